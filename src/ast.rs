@@ -1,3 +1,14 @@
+use nom::combinator::all_consuming;
+use nom::Slice;
+
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use crate::error::{ParseError, SectionLevelWarning};
+use crate::parser::{
+    default_subs, expand_tabs, parse_blocks, parse_document_lossy, parse_inlines, parse_subs,
+};
+use crate::source::DocumentSource;
 use crate::Span;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -9,6 +20,310 @@ pub struct Document<'a> {
     pub content: Blocks<'a>,
 }
 
+/// A document that owns its source, as opposed to [`Document`] which borrows it. It is created
+/// by parsing a string:
+///
+/// ```
+/// use pagliascii::ast::OwnedDocument;
+///
+/// let doc: OwnedDocument = "= Title\n\nThe first paragraph.".parse().unwrap();
+/// assert_eq!(doc.document().content.len(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedDocument {
+    source: String,
+}
+
+impl OwnedDocument {
+    /// The preprocessed source of the document
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The document borrowing from the owned source. The source is parsed anew on every call.
+    pub fn document(&self) -> Document<'_> {
+        parse_document_lossy(&self.source).0
+    }
+}
+
+impl FromStr for OwnedDocument {
+    type Err = ParseError;
+
+    /// Preprocesses and parses `s`, failing with the first error encountered.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = DocumentSource::new(s, |_, _| Err(())).map_err(ParseError::Preprocess)?;
+        let source = source.into_string();
+        let (_, errors) = parse_document_lossy(&source);
+        match errors.into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(OwnedDocument { source }),
+        }
+    }
+}
+
+impl<'a> Document<'a> {
+    /// The default width of a tab used when no `tabsize` attribute is set
+    pub const DEFAULT_TAB_SIZE: usize = 4;
+    /// The number of section levels numbered when no `sectnumlevels` attribute is set
+    pub const DEFAULT_SECTNUMLEVELS: usize = 3;
+
+    /// Compares the structure and text of two documents, regardless of where in their respective
+    /// sources their content is located.
+    ///
+    /// This is what the `PartialEq` impl does as well, as [`Span`]s compare by their text only,
+    /// but spelled out for round-trip tests that rely on it.
+    pub fn semantic_eq(&self, other: &Document<'_>) -> bool {
+        self == other
+    }
+
+    /// The notation of content marked with `stem`, as set by the `stem` header attribute.
+    /// Defaults to AsciiMath.
+    pub fn stem_notation(&self) -> StemNotation {
+        let stem = self
+            .header
+            .as_ref()
+            .and_then(|header| header.attribute("stem"))
+            .and_then(|attr| attr.value.first());
+        match stem.map(|value| value.text().trim()) {
+            Some("latexmath") => StemNotation::LatexMath,
+            _ => StemNotation::AsciiMath,
+        }
+    }
+
+    /// The document type, as set by the `doctype` header attribute. Defaults to article.
+    pub fn doctype(&self) -> Doctype {
+        self.header.as_ref().and_then(DocumentHeader::doctype).unwrap_or_default()
+    }
+
+    /// The width of a tab in columns, as set by the `tabsize` header attribute.
+    pub fn tab_size(&self) -> usize {
+        self.header
+            .as_ref()
+            .and_then(|header| header.attribute("tabsize"))
+            .and_then(|attr| attr.value.first()?.trim().parse().ok())
+            .unwrap_or(Self::DEFAULT_TAB_SIZE)
+    }
+
+    /// The deepest section level that is numbered, as set by the `sectnumlevels` header
+    /// attribute, or `None` if section numbering isn't enabled by the `sectnums` attribute.
+    pub fn section_numbering(&self) -> Option<usize> {
+        let header = self.header.as_ref()?;
+        header.attribute("sectnums")?;
+        let levels = header
+            .attribute("sectnumlevels")
+            .and_then(|attr| attr.value.first()?.trim().parse().ok());
+        Some(levels.unwrap_or(Self::DEFAULT_SECTNUMLEVELS))
+    }
+
+    /// Numbers the sections of the document hierarchically, like `1`, `1.1`, `1.2` and `2`, down
+    /// to sections of level `levels`. Discrete headings and special sections, like appendices
+    /// and the bibliography, are not numbered and neither are their subsections. Parts, the
+    /// level 0 sections of a book, aren't numbered either, but their chapters are numbered
+    /// consecutively across them.
+    pub fn numbered_sections(&self, levels: usize) -> Vec<NumberedSection<'_, 'a>> {
+        let mut sections = Vec::new();
+        number_sections(&self.content, 0, &mut Vec::new(), levels, &mut sections);
+        sections
+    }
+
+    /// The document's description, as set by the `description` header attribute.
+    pub fn description(&self) -> Option<String> {
+        self.header.as_ref()?.attribute_value("description")
+    }
+
+    /// The document's keywords, as set by the comma separated `keywords` header attribute.
+    pub fn keywords(&self) -> Vec<String> {
+        let keywords = self.header.as_ref().and_then(|header| header.attribute_value("keywords"));
+        keywords
+            .iter()
+            .flat_map(|keywords| keywords.split(','))
+            .map(str::trim)
+            .filter(|keyword| !keyword.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// The document's author, as set by the `author` header attribute or the author line.
+    pub fn author_line(&self) -> Option<String> {
+        let header = self.header.as_ref()?;
+        header
+            .attribute_value("author")
+            .or_else(|| Some(header.author.as_ref()?.full_name.text().to_owned()))
+    }
+
+    /// The date of the document's revision, as set by the `revdate` header attribute or the
+    /// revision line.
+    pub fn revdate(&self) -> Option<String> {
+        let header = self.header.as_ref()?;
+        header
+            .attribute_value("revdate")
+            .or_else(|| header.version.as_ref().map(|version| version.date.text().to_owned()))
+            .filter(|date| !date.is_empty())
+    }
+
+    /// The id of a section, which is its explicit id if it has one and otherwise generated from
+    /// its title according to the `idprefix` and `idseparator` header attributes. Returns `None`
+    /// for blocks that aren't sections.
+    pub fn section_id(&self, block: &Block<'_>) -> Option<String> {
+        let title = match &block.context {
+            Context::SectionTitle(title, _) => title,
+            _ => return None,
+        };
+        if let Some(id) = block.id {
+            return Some(id.text().to_owned());
+        }
+        let attribute = |name| self.header.as_ref().and_then(|header| header.attribute_value(name));
+        let prefix = attribute("idprefix").unwrap_or_else(|| "_".to_owned());
+        let separator = attribute("idseparator").unwrap_or_else(|| "_".to_owned());
+        let text = match parse_inlines::<()>(title.content) {
+            Ok((_, tags)) => tags_to_text(&tags),
+            Err(_) => title.content.text().to_owned(),
+        };
+        Some(generate_id(&text, &prefix, &separator))
+    }
+
+    /// The syntax highlighter source listings are meant to be highlighted with, as set by the
+    /// `source-highlighter` header attribute, e.g. `rouge` or `highlight.js`.
+    pub fn source_highlighter(&self) -> Option<String> {
+        self.header
+            .as_ref()?
+            .attribute_value("source-highlighter")
+            .filter(|highlighter| !highlighter.is_empty())
+    }
+
+    /// The syntax highlighter to highlight `block` with, which is the document's
+    /// [`source_highlighter`](Self::source_highlighter) if the block is a source listing.
+    pub fn highlighter_for(&self, block: &Block<'_>) -> Option<String> {
+        self.source_highlighter().filter(|_| block.is_source())
+    }
+
+    /// Resolves the text of a cross reference to the block with the given id, which is the
+    /// block's `reftext` attribute if set and its title otherwise.
+    pub fn reftext(&self, id: &str) -> Option<String> {
+        let block = find_block(&self.content, id)?;
+        if let Some(&Some(reftext)) = block.attributes.get("reftext") {
+            return Some(reftext.to_owned());
+        }
+        match &block.context {
+            Context::SectionTitle(title, _) => Some(title.content.text().to_owned()),
+            _ => block.title.map(|title| title.text().to_owned()),
+        }
+    }
+}
+
+fn find_block<'b, 'a>(blocks: &'b [Block<'a>], id: &str) -> Option<&'b Block<'a>> {
+    blocks.iter().find_map(|block| {
+        let block_id =
+            block.id.map(|span| span.text()).or_else(|| block.attributes.get("id")?.as_deref());
+        if block_id == Some(id) {
+            return Some(block);
+        }
+        match &block.context {
+            Context::SectionTitle(_, blocks)
+            | Context::Admonition { blocks, .. }
+            | Context::Example(blocks)
+            | Context::Sidebar(blocks)
+            | Context::Quote(blocks)
+            | Context::Open(blocks) => find_block(blocks, id),
+            Context::List(list) => list.items.iter().find_map(|item| find_block(&item.blocks, id)),
+            _ => None,
+        }
+    })
+}
+
+/// Generates an id from the plain text of a title, like `_the_best_section` for `The Best
+/// Section`. The text is lowercased and every run of characters other than letters and digits
+/// is replaced by a single `separator`, dropping those at the end.
+pub fn generate_id(text: &str, prefix: &str, separator: &str) -> String {
+    let mut id = prefix.to_owned();
+    let mut pending_separator = false;
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            if pending_separator {
+                id.push_str(separator);
+                pending_separator = false;
+            }
+            id.push(c);
+        } else {
+            // separators are only put between words
+            pending_separator = id.len() > prefix.len();
+        }
+    }
+    id
+}
+
+/// Reports the sections of a document that skip a level, being nested more than one level deeper
+/// than their parent section or, for top-level sections, the document title. Discrete headings
+/// may use any level.
+pub fn validate_section_levels(doc: &Document<'_>) -> Vec<SectionLevelWarning> {
+    let mut warnings = Vec::new();
+    validate_levels(&doc.content, 0, &mut warnings);
+    warnings
+}
+
+fn validate_levels(blocks: &[Block<'_>], parent: usize, warnings: &mut Vec<SectionLevelWarning>) {
+    for block in blocks {
+        let (title, blocks) = match &block.context {
+            Context::SectionTitle(title, blocks) => (title, blocks),
+            _ => continue,
+        };
+        if title.style == Some(SectionStyle::Discrete) {
+            continue;
+        }
+        if title.level > parent + 1 {
+            warnings.push(SectionLevelWarning {
+                line: title.content.location_line(),
+                range: title.content.byte_range(),
+                expected: parent + 1,
+                found: title.level,
+            });
+        }
+        validate_levels(blocks, title.level, warnings);
+    }
+}
+
+/// A section along with its number, see [`Document::numbered_sections`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NumberedSection<'b, 'a> {
+    /// The components of the section number, e.g. `[1, 2]` for section `1.2`
+    pub number: Vec<usize>,
+    pub title: &'b SectionTitle<'a>,
+}
+
+impl NumberedSection<'_, '_> {
+    /// The section number as it is displayed, e.g. `1.2.`
+    pub fn label(&self) -> String {
+        self.number.iter().map(|number| format!("{}.", number)).collect()
+    }
+}
+
+fn number_sections<'b, 'a>(
+    blocks: &'b [Block<'a>],
+    depth: usize,
+    counters: &mut Vec<usize>,
+    levels: usize,
+    sections: &mut Vec<NumberedSection<'b, 'a>>,
+) {
+    for block in blocks {
+        let (title, blocks) = match &block.context {
+            Context::SectionTitle(title, blocks) => (title, blocks),
+            _ => continue,
+        };
+        if title.style.is_some() || title.level > levels {
+            continue;
+        }
+        if title.level == 0 {
+            number_sections(blocks, depth, counters, levels, sections);
+            continue;
+        }
+        counters.truncate(depth + 1);
+        counters.resize(depth + 1, 0);
+        counters[depth] += 1;
+        sections.push(NumberedSection { number: counters.clone(), title });
+        number_sections(blocks, depth + 1, counters, levels, sections);
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct DocumentHeader<'a> {
     /// The document's level-0 title
@@ -21,6 +336,42 @@ pub struct DocumentHeader<'a> {
     pub attributes: Vec<DocAttribute<'a>>,
 }
 
+impl<'a> DocumentHeader<'a> {
+    /// The effective entry of the attribute `id`, which is the last one to set it. Returns `None`
+    /// if the attribute isn't set or was unset last.
+    pub fn attribute(&self, id: &str) -> Option<&DocAttribute<'a>> {
+        self.attributes.iter().rev().find(|attr| attr.id.text() == id).filter(|attr| !attr.unset)
+    }
+
+    /// The value of the attribute `id`, with the lines of a multi-line value joined by spaces.
+    pub fn attribute_value(&self, id: &str) -> Option<String> {
+        let attr = self.attribute(id)?;
+        Some(attr.value.iter().map(|line| line.trim().text()).collect::<Vec<_>>().join(" "))
+    }
+
+    /// The document type set by the `doctype` attribute, if it is set to a known one.
+    pub fn doctype(&self) -> Option<Doctype> {
+        Doctype::from_value(self.attribute("doctype")?.value.first()?.text().trim())
+    }
+
+    /// Splits the title into the main title and a subtitle at the last occurrence of the
+    /// `title-separator` attribute followed by a space, which defaults to `:`.
+    pub fn split_title(&self) -> (Span<'a>, Option<Span<'a>>) {
+        let separator = self
+            .attribute("title-separator")
+            .and_then(|attr| attr.value.first())
+            .map_or(":", |value| value.text().trim());
+        let title = self.title;
+        match title.rfind(&format!("{} ", separator)) {
+            Some(idx) if !separator.is_empty() => {
+                let subtitle = title.slice(idx + separator.len()..);
+                (title.slice(..idx).trim_end(), Some(subtitle.trim_start()))
+            }
+            _ => (title, None),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Author<'a> {
     /// Full name of the author, e.g. "John Doe"
@@ -43,12 +394,112 @@ pub type AttributeList<'a> = indexmap::IndexMap<&'a str, Option<&'a str>>;
 pub struct Block<'a> {
     /// The blocks context ie. its type
     pub context: Context<'a>,
+    /// The blocks title, e.g. `My Listing` in `.My Listing`
+    pub title: Option<Span<'a>>,
     /// The blocks attributes
     pub attributes: AttributeList<'a>,
+    /// The blocks id, e.g. `intro` in `[#intro]`
+    pub id: Option<Span<'a>>,
+    /// The blocks roles, e.g. `lead` in `[.lead]`
+    pub roles: Vec<Span<'a>>,
+    /// The blocks options, e.g. `header` in `[%header]`
+    pub options: Vec<Span<'a>>,
     /// An optional trailing callouts element
     pub callouts: Vec<Callout<'a>>,
 }
 
+impl<'a> Block<'a> {
+    /// Creates a block of the given context without any attributes or callouts
+    pub fn new(context: Context<'a>) -> Self {
+        Block {
+            context,
+            title: None,
+            attributes: AttributeList::default(),
+            id: None,
+            roles: Vec::new(),
+            options: Vec::new(),
+            callouts: Vec::new(),
+        }
+    }
+
+    /// Whether this is a source listing, a listing block styled with `[source]`.
+    pub fn is_source(&self) -> bool {
+        matches!(self.context, Context::Listing(_))
+            && matches!(self.attributes.get_index(0), Some((&"source", None)))
+    }
+
+    /// The language of a source listing, like `rust` in `[source,rust]`, taken from the second
+    /// positional or the `language` attribute.
+    pub fn source_language(&self) -> Option<&'a str> {
+        if !self.is_source() {
+            return None;
+        }
+        match self.attributes.get("language") {
+            Some(&language) => language,
+            None => match self.attributes.get_index(1) {
+                Some((&language, None)) => Some(language),
+                _ => None,
+            },
+        }
+    }
+
+    /// The title of the block parsed into inlines, so that formatting like the emphasis in
+    /// `.A _titled_ figure` is kept. The title is parsed anew on every call.
+    pub fn title_tags(&self) -> Option<Tags<'a>> {
+        let title = self.title?;
+        Some(parse_inlines::<()>(title).map_or_else(|_| vec![Tag::Text(title)], |(_, tags)| tags))
+    }
+
+    /// The options set with the `%name` shorthand, like `nowrap` in `[source%nowrap]`
+    pub fn options(&self) -> &[Span<'a>] {
+        &self.options
+    }
+
+    /// Whether the option `name` is set, either with the `%name` shorthand or in the comma
+    /// separated `options` or `opts` attribute.
+    pub fn has_option(&self, name: &str) -> bool {
+        self.options.iter().any(|option| option.text() == name)
+            || ["options", "opts"].iter().any(|attribute| {
+                self.attributes
+                    .get(attribute)
+                    .copied()
+                    .flatten()
+                    .is_some_and(|options| options.split(',').any(|it| it.trim() == name))
+            })
+    }
+
+    /// The content of a listing or literal block with its tabs expanded according to the block's
+    /// `tabsize` attribute, falling back to `tab_size`, usually [`Document::tab_size`]. A tab
+    /// size of 0 keeps the tabs.
+    pub fn verbatim_content(&self, tab_size: usize) -> Option<Cow<'a, str>> {
+        match self.context {
+            Context::Listing(content) | Context::Literal(content) => {
+                let tab_size = self
+                    .attributes
+                    .get("tabsize")
+                    .copied()
+                    .flatten()
+                    .and_then(|size| size.trim().parse().ok())
+                    .unwrap_or(tab_size);
+                Some(expand_tabs(content.text(), tab_size))
+            }
+            _ => None,
+        }
+    }
+
+    /// The substitutions applied to the text of the block, as set by its `subs` attribute, like
+    /// `[subs=attributes]` on a passthrough block. Falls back to the default substitutions of
+    /// the block's context if the attribute is missing or invalid.
+    pub fn subs(&self) -> Subs {
+        self.attributes
+            .get("subs")
+            .copied()
+            .flatten()
+            .and_then(|subs| all_consuming(parse_subs::<()>)(Span::new(subs.trim())).ok())
+            .map_or_else(|| default_subs(&self.context), |(_, subs)| subs)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Callout<'a> {
     pub number: usize,
@@ -58,7 +509,7 @@ pub struct Callout<'a> {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Context<'a> {
     /// A heading, e.g. `== Now for something else`
-    SectionTitle(Span<'a>, Vec<Block<'a>>),
+    SectionTitle(SectionTitle<'a>, Vec<Block<'a>>),
     Admonition {
         label: Span<'a>,
         blocks: Vec<Block<'a>>,
@@ -69,9 +520,23 @@ pub enum Context<'a> {
     Listing(Span<'a>),
     Literal(Span<'a>),
     Paragraph(Span<'a>),
+    /// A paragraph in a bibliography section starting with a `[[[id]]]` anchor, its content being
+    /// the text following the anchor
+    BibliographyEntry {
+        entry: BibliographyEntry<'a>,
+        content: Span<'a>,
+    },
     Passthrough(Span<'a>),
-    Quote(Span<'a>),
-    Verse(Span<'a>),
+    /// A math block, a passthrough block styled with `[stem]`, `[latexmath]` or `[asciimath]`
+    Stem {
+        notation: Option<StemNotation>,
+        content: Span<'a>,
+    },
+    /// A quote block delimited by `____`, its attribution and citation being the second and third
+    /// positional attributes
+    Quote(Vec<Block<'a>>),
+    /// A verse, a quote block or paragraph styled with `[verse]`
+    Verse(Verse<'a>),
     List(List<'a>),
     Table(Table<'a>),
     /// A block macro, `image::foo.png[]`
@@ -80,6 +545,12 @@ pub enum Context<'a> {
     ThematicBreak,
     /// A page break, `<<<`
     PageBreak,
+    /// A region of the input that could not be parsed, only produced by
+    /// [`parse_document_lossy`](crate::parser::parse_document_lossy)
+    Error(Span<'a>),
+    /// A line comment, `// like this`, or the content of a comment block delimited by `////`.
+    /// Only kept if [`ParseOptions::preserve_comments`](crate::parser::ParseOptions) is set
+    Comment(Span<'a>),
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -87,11 +558,207 @@ pub struct Macro<'a> {
     pub name: Span<'a>,
     pub target: Span<'a>,
     pub attribute_list: AttributeList<'a>,
+    /// The positional attributes by their position among all entries of the attribute list,
+    /// `None` for named and empty entries, so `[Logo,width=200,100]` has `100` at index 2
+    pub positional: Vec<Option<&'a str>>,
+}
+
+/// The attributes of a block or inline image macro, like `image::logo.png[Logo,200,100]`
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ImageAttributes<'a> {
+    /// The alternative text, the first positional attribute
+    pub alt: Option<&'a str>,
+    /// The width, the second positional attribute
+    pub width: Option<&'a str>,
+    /// The height, the third positional attribute
+    pub height: Option<&'a str>,
+    /// The target the image links to
+    pub link: Option<&'a str>,
+    /// `left` or `right`, the side the image floats on
+    pub float: Option<&'a str>,
+    /// `left`, `center` or `right`, the horizontal alignment of a block image
+    pub align: Option<&'a str>,
+    pub title: Option<&'a str>,
+}
+
+impl<'a> From<&Macro<'a>> for ImageAttributes<'a> {
+    /// Reads the image attributes from an image macro, named attributes taking precedence over
+    /// positional ones.
+    fn from(image: &Macro<'a>) -> Self {
+        let named = |name| image.attribute_list.get(name).copied().flatten();
+        let positional =
+            |idx: usize, name| named(name).or_else(|| image.positional.get(idx).copied().flatten());
+        ImageAttributes {
+            alt: positional(0, "alt"),
+            width: positional(1, "width"),
+            height: positional(2, "height"),
+            link: named("link"),
+            float: named("float"),
+            align: named("align"),
+            title: named("title"),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Table<'a> {
-    __: &'a (),
+    /// The column specifications, taken from the `cols` attribute or derived from the first row
+    pub columns: Vec<ColumnSpec>,
+    /// The header row, if any
+    pub header: Option<Vec<TableCell<'a>>>,
+    pub rows: Vec<Vec<TableCell<'a>>>,
+    /// The footer row, if the `footer` option is set
+    pub footer: Option<Vec<TableCell<'a>>>,
+    /// Whether the `autowidth` option is set, sizing the columns to fit their content
+    pub autowidth: bool,
+    /// The `width` attribute, e.g. `75%`
+    pub width: Option<&'a str>,
+    pub frame: Frame,
+    pub grid: Grid,
+}
+
+/// The borders drawn around a table, set by the `frame` attribute.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Frame {
+    /// `all`
+    #[default]
+    All,
+    /// `topbot` or `ends`
+    Ends,
+    /// `sides`
+    Sides,
+    /// `none`
+    None,
+}
+
+impl Frame {
+    pub fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "all" => Some(Frame::All),
+            "topbot" | "ends" => Some(Frame::Ends),
+            "sides" => Some(Frame::Sides),
+            "none" => Some(Frame::None),
+            _ => None,
+        }
+    }
+}
+
+/// The borders drawn between the cells of a table, set by the `grid` attribute.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Grid {
+    /// `all`
+    #[default]
+    All,
+    /// `rows`
+    Rows,
+    /// `cols`
+    Cols,
+    /// `none`
+    None,
+}
+
+impl Grid {
+    pub fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "all" => Some(Grid::All),
+            "rows" => Some(Grid::Rows),
+            "cols" => Some(Grid::Cols),
+            "none" => Some(Grid::None),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TableCell<'a> {
+    pub content: Span<'a>,
+    /// The style set on the cell itself, like `a` in `a|`, overriding the column's style
+    pub style: Option<ColumnStyle>,
+}
+
+impl<'a> TableCell<'a> {
+    /// The content of the cell with escaped pipes, `\|`, unescaped.
+    pub fn text(&self) -> Cow<'a, str> {
+        let content = self.content.text();
+        if content.contains("\\|") {
+            Cow::Owned(content.replace("\\|", "|"))
+        } else {
+            Cow::Borrowed(content)
+        }
+    }
+
+    /// Parses the content of the cell as blocks, as is done for cells of the AsciiDoc style.
+    pub fn blocks(&self) -> Blocks<'a> {
+        parse_blocks::<()>(self.content).map(|(_, blocks)| blocks).unwrap_or_default()
+    }
+}
+
+/// The specification of a table column, e.g. `^.>2m`. These apply to every cell in the column.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ColumnSpec {
+    pub width: ColumnWidth,
+    pub halign: HorizontalAlignment,
+    pub valign: VerticalAlignment,
+    pub style: ColumnStyle,
+}
+
+impl Default for ColumnSpec {
+    fn default() -> Self {
+        ColumnSpec {
+            width: ColumnWidth::Proportional(1),
+            halign: HorizontalAlignment::Left,
+            valign: VerticalAlignment::Top,
+            style: ColumnStyle::Default,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColumnWidth {
+    /// A width relative to the other columns, e.g. `2`
+    Proportional(usize),
+    /// A percentage of the table width, e.g. `25%`
+    Percentage(usize),
+    /// `~`, the column is sized to fit its content
+    Auto,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HorizontalAlignment {
+    /// `<`
+    Left,
+    /// `^`
+    Center,
+    /// `>`
+    Right,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VerticalAlignment {
+    /// `.<`
+    Top,
+    /// `.^`
+    Middle,
+    /// `.>`
+    Bottom,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColumnStyle {
+    /// `a`, the content is parsed as an AsciiDoc document
+    AsciiDoc,
+    /// `d`, the content is treated as paragraphs
+    Default,
+    /// `e`, the content is emphasized
+    Emphasis,
+    /// `h`, the cells are styled as header cells
+    Header,
+    /// `l`, the content is treated as a literal block
+    Literal,
+    /// `m`, the content is rendered in monospace
+    Monospace,
+    /// `s`, the content is rendered in bold
+    Strong,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -105,6 +772,63 @@ pub enum ListItemKind<'a> {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct List<'a> {
     pub items: Vec<ListItem<'a>>,
+    /// The list's style, taken from the first positional attribute
+    pub style: Option<ListStyle>,
+    /// The number of the first item of an ordered list, taken from the `start` attribute
+    pub start: Option<i64>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ListStyle {
+    /// `[horizontal]`, terms and definitions are laid out side by side
+    Horizontal,
+    /// `[qanda]`, terms are questions and definitions are their answers
+    Qanda,
+    /// `[arabic]`, ordered items are numbered 1, 2, 3
+    Arabic,
+    /// `[decimal]`, ordered items are numbered 01, 02, 03
+    Decimal,
+    /// `[loweralpha]`, ordered items are numbered a, b, c
+    LowerAlpha,
+    /// `[upperalpha]`, ordered items are numbered A, B, C
+    UpperAlpha,
+    /// `[lowerroman]`, ordered items are numbered i, ii, iii
+    LowerRoman,
+    /// `[upperroman]`, ordered items are numbered I, II, III
+    UpperRoman,
+    /// `[lowergreek]`, ordered items are numbered α, β, γ
+    LowerGreek,
+}
+
+impl ListStyle {
+    /// Reads the list style from the first positional attribute of a block attribute list
+    pub fn from_attributes(attributes: &AttributeList<'_>) -> Option<ListStyle> {
+        match attributes.get_index(0)? {
+            (&"horizontal", None) => Some(ListStyle::Horizontal),
+            (&"qanda", None) => Some(ListStyle::Qanda),
+            (&"arabic", None) => Some(ListStyle::Arabic),
+            (&"decimal", None) => Some(ListStyle::Decimal),
+            (&"loweralpha", None) => Some(ListStyle::LowerAlpha),
+            (&"upperalpha", None) => Some(ListStyle::UpperAlpha),
+            (&"lowerroman", None) => Some(ListStyle::LowerRoman),
+            (&"upperroman", None) => Some(ListStyle::UpperRoman),
+            (&"lowergreek", None) => Some(ListStyle::LowerGreek),
+            _ => None,
+        }
+    }
+
+    /// The value of the HTML `type` attribute of an ordered list with this style, if there is
+    /// one.
+    pub fn html_type(self) -> Option<&'static str> {
+        match self {
+            ListStyle::Arabic => Some("1"),
+            ListStyle::LowerAlpha => Some("a"),
+            ListStyle::UpperAlpha => Some("A"),
+            ListStyle::LowerRoman => Some("i"),
+            ListStyle::UpperRoman => Some("I"),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -114,6 +838,56 @@ pub struct ListItem<'a> {
     /// Empty in case of a description list
     pub paragraph: Tags<'a>,
     pub blocks: Blocks<'a>,
+    /// The anchor of an item in a bibliography section, the paragraph holding the text following
+    /// it
+    pub bibliography: Option<BibliographyEntry<'a>>,
+}
+
+/// A verse, whose content is kept with its line breaks, like
+///
+/// ```text
+/// [verse, Carl Sandburg, Fog]
+/// ____
+/// The fog comes
+/// on little cat feet.
+/// ____
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Verse<'a> {
+    pub content: Span<'a>,
+    /// The author, taken from the second positional or the `attribution` attribute
+    pub attribution: Option<Span<'a>>,
+    /// The source, taken from the third positional or the `citation` attribute
+    pub citation: Option<Span<'a>>,
+}
+
+impl<'a> Verse<'a> {
+    /// Creates a verse from its content and the attributes of its block, whose values are sliced
+    /// out of `metadata`, the span the block's attribute list was parsed from.
+    pub fn new(content: Span<'a>, attributes: &AttributeList<'a>, metadata: Span<'a>) -> Self {
+        let attribute = |name, idx| match attributes.get(name) {
+            Some(&value) => value,
+            None => match attributes.get_index(idx) {
+                Some((&positional, None)) => Some(positional),
+                _ => None,
+            },
+        };
+        Verse {
+            content,
+            attribution: attribute("attribution", 1).and_then(|it| metadata.sub_span(it)),
+            citation: attribute("citation", 2).and_then(|it| metadata.sub_span(it)),
+        }
+    }
+}
+
+/// The `[[[id]]]` anchor starting an entry of a bibliography, like
+/// `[[[knuth]]] Donald Knuth, The Art of Computer Programming`, which defines a reference that
+/// can be cited by its id.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BibliographyEntry<'a> {
+    pub id: Span<'a>,
+    /// The text used to cite the entry, `[[[id,reftext]]]`
+    pub reftext: Option<Span<'a>>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -122,14 +896,57 @@ pub struct SectionTitle<'a> {
     pub level: usize,
     /// Contents of the section title
     pub content: Span<'a>,
+    /// The style of the section, e.g. `appendix` in `[appendix]`
+    pub style: Option<SectionStyle>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SectionStyle {
+    /// `[discrete]` or `[float]`, a heading that doesn't open a section
+    Discrete,
+    Abstract,
+    Acknowledgments,
+    Appendix,
+    Bibliography,
+    Colophon,
+    Dedication,
+    Glossary,
+    Index,
+    Preface,
+}
+
+impl SectionStyle {
+    /// Reads the section style from the first positional attribute of a block attribute list
+    pub fn from_attributes(attributes: &AttributeList<'_>) -> Option<SectionStyle> {
+        let style = match attributes.get_index(0)? {
+            (style, None) => *style,
+            _ => return None,
+        };
+        Some(match style {
+            "discrete" | "float" => SectionStyle::Discrete,
+            "abstract" => SectionStyle::Abstract,
+            "acknowledgments" => SectionStyle::Acknowledgments,
+            "appendix" => SectionStyle::Appendix,
+            "bibliography" => SectionStyle::Bibliography,
+            "colophon" => SectionStyle::Colophon,
+            "dedication" => SectionStyle::Dedication,
+            "glossary" => SectionStyle::Glossary,
+            "index" => SectionStyle::Index,
+            "preface" => SectionStyle::Preface,
+            _ => return None,
+        })
+    }
 }
 
 /// A list of tags
 pub type Tags<'a> = Vec<Tag<'a>>;
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Tag<'a> {
     /// A text node
     Text(Span<'a>),
+    /// A hard line break
+    LineBreak,
     /// An anchor
     Anchor(Span<'a>),
     /// Formatting node
@@ -140,6 +957,75 @@ pub enum Tag<'a> {
     Link(Link<'a>),
     /// An inline macro, like `image:play.png[]`
     InlineMacro(Macro<'a>),
+    /// An index term, like `((visible))`, `(((primary,secondary)))` or `indexterm:[primary]`
+    IndexTerm {
+        primary: Span<'a>,
+        secondary: Option<Span<'a>>,
+        tertiary: Option<Span<'a>>,
+        /// Whether the primary term is also part of the text, as is the case for `((term))` and
+        /// `indexterm2:[term]`
+        visible: bool,
+    },
+    /// A footnote, which is a definition like `footnote:disclaimer[Some text]` if it has content
+    /// and a reference to the footnote with the same id like `footnote:disclaimer[]` otherwise
+    Footnote { id: Option<Span<'a>>, content: Option<Tags<'a>> },
+    /// A button, like `btn:[OK]`
+    Button(Span<'a>),
+    /// A menu selection, like `menu:View[Zoom > Reset]`, starting with the top-level menu and
+    /// ending with the selected item
+    Menu(Vec<Span<'a>>),
+    /// Inline math, like `stem:[sqrt(4)]` or `latexmath:[\sqrt{4}]`
+    Stem { notation: Option<StemNotation>, content: Span<'a> },
+    /// An inline passthrough, like `+++<u>text</u>+++` or `pass:q[<u>*text*</u>]`
+    Passthrough {
+        /// The substitutions that still apply to the content
+        subs: Subs,
+        /// The content that is passed through
+        content: Span<'a>,
+    },
+}
+
+/// Concatenates the text of a tag tree, dropping all markup. Links and inline macros contribute
+/// their display text while footnotes and index terms are left out entirely.
+pub fn tags_to_text(tags: &[Tag<'_>]) -> String {
+    let mut text = String::new();
+    push_tags_text(&mut text, tags);
+    text
+}
+
+fn push_tags_text(text: &mut String, tags: &[Tag<'_>]) {
+    for tag in tags {
+        match tag {
+            Tag::Text(span)
+            | Tag::Passthrough { content: span, .. }
+            | Tag::Stem { content: span, .. } => text.push_str(span),
+            Tag::LineBreak => text.push('\n'),
+            Tag::Anchor(_) => (),
+            Tag::Format(_, tags) => push_tags_text(text, tags),
+            Tag::Mark(mark) => push_tags_text(text, &mark.content),
+            Tag::Link(Link { content: Some(tags), .. }) => push_tags_text(text, tags),
+            Tag::Link(Link { href, .. }) => text.push_str(href),
+            Tag::IndexTerm { primary, visible: true, .. } => text.push_str(primary),
+            Tag::IndexTerm { .. } | Tag::Footnote { .. } => (),
+            Tag::Button(label) => text.push_str(label),
+            Tag::Menu(path) => {
+                for (idx, item) in path.iter().enumerate() {
+                    if idx > 0 {
+                        text.push_str(" > ");
+                    }
+                    text.push_str(item);
+                }
+            }
+            Tag::InlineMacro(Macro { name, .. })
+                if matches!(name.text(), "footnote" | "footnoteref") => {}
+            Tag::InlineMacro(Macro { target, attribute_list, .. }) => {
+                match attribute_list.get_index(0) {
+                    Some((display, None)) => text.push_str(display),
+                    _ => text.push_str(target),
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -156,6 +1042,90 @@ pub enum FormatKind {
     Subscript,
 }
 
+/// The type of a document, determining its structure
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Doctype {
+    #[default]
+    Article,
+    Book,
+    /// A man page, which requires a document title
+    Manpage,
+    /// A document consisting of a single paragraph
+    Inline,
+}
+
+impl Doctype {
+    pub fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "article" => Some(Doctype::Article),
+            "book" => Some(Doctype::Book),
+            "manpage" => Some(Doctype::Manpage),
+            "inline" => Some(Doctype::Inline),
+            _ => None,
+        }
+    }
+
+    /// Whether documents of this type must have a document title
+    pub fn requires_title(self) -> bool {
+        self == Doctype::Manpage
+    }
+}
+
+/// The notation of math content. `None` is used for content marked with `stem`, whose notation
+/// is determined by the `stem` document attribute, see [`Document::stem_notation`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StemNotation {
+    LatexMath,
+    AsciiMath,
+}
+
+impl StemNotation {
+    /// Maps a block style or inline macro name to its notation, `stem` being `Some(None)`.
+    pub fn from_style(style: &str) -> Option<Option<StemNotation>> {
+        match style {
+            "stem" => Some(None),
+            "latexmath" => Some(Some(StemNotation::LatexMath)),
+            "asciimath" => Some(Some(StemNotation::AsciiMath)),
+            _ => None,
+        }
+    }
+}
+
+/// A list of substitutions, applied in order
+pub type Subs = Vec<Substitution>;
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Substitution {
+    /// `c`, replaces `<`, `>` and `&` with their character references
+    SpecialCharacters,
+    /// `q`, applies inline formatting like `*bold*`
+    Quotes,
+    /// `a`, replaces attribute references like `{author}`
+    Attributes,
+    /// `r`, replaces textual symbols like `(C)`
+    Replacements,
+    /// `m`, processes inline macros like `image:play.png[]`
+    Macros,
+    /// `p`, processes hard line breaks like ` +`
+    PostReplacements,
+    /// Processes callouts like `<1>`, only part of the verbatim group
+    Callouts,
+}
+
+impl Substitution {
+    /// The `normal` substitution group
+    pub const NORMAL: &'static [Substitution] = &[
+        Substitution::SpecialCharacters,
+        Substitution::Quotes,
+        Substitution::Attributes,
+        Substitution::Replacements,
+        Substitution::Macros,
+        Substitution::PostReplacements,
+    ];
+    /// The `verbatim` substitution group
+    pub const VERBATIM: &'static [Substitution] =
+        &[Substitution::SpecialCharacters, Substitution::Callouts];
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Mark<'a> {
     /// Contents of the mark, may contain markup
@@ -193,3 +1163,317 @@ pub struct DocAttribute<'a> {
     /// The attribute value which may span multiple lines
     pub value: Vec<Span<'a>>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::PreprocessError;
+
+    #[test]
+    fn test_reftext() {
+        let (doc, errors) = parse_document_lossy(
+            r"= Document
+
+[#intro]
+== Introduction
+
+Some text.
+
+[#details, reftext=The Details]
+== Details of the Matter
+",
+        );
+        assert!(errors.is_empty());
+        assert_eq!(doc.reftext("intro").as_deref(), Some("Introduction"));
+        assert_eq!(doc.reftext("details").as_deref(), Some("The Details"));
+        assert_eq!(doc.reftext("missing"), None);
+    }
+
+    #[test]
+    fn test_split_title() {
+        let split_title = |input| {
+            let (doc, _) = parse_document_lossy(input);
+            let (title, subtitle) = doc.header.unwrap().split_title();
+            (title.text(), subtitle.map(|it| it.text()))
+        };
+        assert_eq!(split_title("= Main Title\n"), ("Main Title", None));
+        assert_eq!(
+            split_title("= Main Title: A Subtitle: Part Two\n"),
+            ("Main Title: A Subtitle", Some("Part Two"))
+        );
+        assert_eq!(
+            split_title("= Main Title - A Subtitle\n:title-separator: -\n"),
+            ("Main Title", Some("A Subtitle"))
+        );
+    }
+
+    #[test]
+    fn test_semantic_eq() {
+        let (doc, _) = parse_document_lossy("= Document\n\n== Section\n\nSome *text*.\n");
+        let (shifted, _) =
+            parse_document_lossy("\n\n= Document\n\n\n\n== Section\nSome *text*.\n\n");
+        let (different, _) = parse_document_lossy("= Document\n\n== Section\n\nOther text.\n");
+        assert!(doc.semantic_eq(&shifted));
+        assert!(!doc.semantic_eq(&different));
+
+        // the equal content really is located at different offsets
+        let title_range = |doc: &Document<'_>| match &doc.content[0].context {
+            Context::SectionTitle(title, _) => title.content.byte_range(),
+            _ => unreachable!(),
+        };
+        assert_eq!(title_range(&doc), 15..22);
+        assert_eq!(title_range(&shifted), 19..26);
+    }
+
+    #[test]
+    fn test_stem_notation() {
+        let (doc, _) = parse_document_lossy("= Document\n:stem:\n");
+        assert_eq!(doc.stem_notation(), super::StemNotation::AsciiMath);
+        let (doc, _) = parse_document_lossy("= Document\n:stem: latexmath\n");
+        assert_eq!(doc.stem_notation(), super::StemNotation::LatexMath);
+    }
+
+    #[test]
+    fn test_owned_document() {
+        let doc: OwnedDocument = "= Title\n\nSome text.\n".parse().unwrap();
+        let (expected, _) = parse_document_lossy("= Title\n\nSome text.\n");
+        assert!(doc.document().semantic_eq(&expected));
+        assert!(matches!(
+            "include::other.adoc[]".parse::<OwnedDocument>(),
+            Err(ParseError::Preprocess(PreprocessError::IncludeError { .. }))
+        ));
+        assert!(matches!(
+            "image::broken.png[width=240\n".parse::<OwnedDocument>(),
+            Err(ParseError::InvalidBlock { .. })
+        ));
+    }
+
+    #[test]
+    fn test_doctype() {
+        let (doc, _) = parse_document_lossy("= Document\n");
+        assert_eq!(doc.doctype(), Doctype::Article);
+        let (doc, _) = parse_document_lossy("= Document\n:doctype: manpage\n");
+        assert_eq!(doc.doctype(), Doctype::Manpage);
+    }
+
+    #[test]
+    fn test_metadata() {
+        let source = r"= Document
+Jane Doe
+v1.0, 2021-01-31
+:description: A document about documents.
+:keywords: asciidoc, parsing,  metadata
+";
+        let (doc, _) = parse_document_lossy(source);
+        assert_eq!(doc.description().as_deref(), Some("A document about documents."));
+        assert_eq!(doc.keywords(), ["asciidoc", "parsing", "metadata"]);
+        assert_eq!(doc.author_line().as_deref(), Some("Jane Doe"));
+        assert_eq!(doc.revdate().as_deref(), Some("2021-01-31"));
+
+        let (doc, _) = parse_document_lossy("= Document\n:author: John Doe\n:revdate: today\n");
+        assert_eq!(doc.description(), None);
+        assert!(doc.keywords().is_empty());
+        assert_eq!(doc.author_line().as_deref(), Some("John Doe"));
+        assert_eq!(doc.revdate().as_deref(), Some("today"));
+    }
+
+    #[test]
+    fn test_source_highlighter() {
+        let source = r"= Document
+:source-highlighter: rouge
+
+[source,rust]
+----
+fn main() {}
+----
+
+----
+plain listing
+----
+";
+        let (doc, _) = parse_document_lossy(source);
+        assert_eq!(doc.source_highlighter().as_deref(), Some("rouge"));
+        let (source, plain) = (&doc.content[0], &doc.content[1]);
+        assert_eq!(source.source_language(), Some("rust"));
+        assert_eq!(doc.highlighter_for(source).as_deref(), Some("rouge"));
+        assert_eq!(plain.source_language(), None);
+        assert_eq!(doc.highlighter_for(plain), None);
+
+        let (doc, _) = parse_document_lossy("= Document\n\n[source,rust]\n----\nx\n----\n");
+        assert_eq!(doc.highlighter_for(&doc.content[0]), None);
+    }
+
+    #[test]
+    fn test_title_tags() {
+        let (doc, _) = parse_document_lossy(".Figure _caption_\nimage::figure.png[]\n");
+        let image = &doc.content[0];
+        assert!(
+            matches!(image.context, Context::BlockMacro(Macro { name, .. }) if name.text() == "image")
+        );
+        let title = image.title_tags().unwrap();
+        let expected = vec![
+            Tag::Text(Span::new("Figure ")),
+            Tag::Format(FormatKind::Italic, vec![Tag::Text(Span::new("caption"))]),
+        ];
+        assert_eq!(title, expected);
+        assert_eq!(Block::new(Context::ThematicBreak).title_tags(), None);
+    }
+
+    #[test]
+    fn test_section_id() {
+        let source = r"= Document
+
+== The *Best* _Section_
+
+[[explicit]]
+== What's new? (v2.0)
+";
+        let (doc, _) = parse_document_lossy(source);
+        let ids: Vec<_> = doc.content.iter().filter_map(|block| doc.section_id(block)).collect();
+        assert_eq!(ids, ["_the_best_section", "explicit"]);
+        assert_eq!(generate_id("What's new? (v2.0)", "_", "_"), "_what_s_new_v2_0");
+        assert_eq!(generate_id("  Leading -- and trailing!  ", "", "-"), "leading-and-trailing");
+
+        let source = "= Document\n:idprefix: sec-\n:idseparator: -\n\n== A `Code` Title\n";
+        let (doc, _) = parse_document_lossy(source);
+        assert_eq!(doc.section_id(&doc.content[0]).as_deref(), Some("sec-a-code-title"));
+    }
+
+    #[test]
+    fn test_validate_section_levels() {
+        let source = r"= Document
+
+== Chapter
+
+=== Section
+
+[discrete]
+===== Discrete
+
+== Next Chapter
+";
+        let (doc, _) = parse_document_lossy(source);
+        assert_eq!(validate_section_levels(&doc), []);
+
+        let source = r"= Document
+
+== Chapter
+
+==== Too Deep
+
+===== Deeper Still
+";
+        let (doc, _) = parse_document_lossy(source);
+        let warnings = validate_section_levels(&doc);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(&source[warnings[0].range.clone()], "Too Deep");
+        assert_eq!(
+            warnings[0],
+            SectionLevelWarning { line: 5, range: 29..37, expected: 2, found: 3 }
+        );
+    }
+
+    #[test]
+    fn test_numbered_sections() {
+        let source = r"= Document
+:sectnums:
+
+== First
+
+=== First Sub
+
+=== Second Sub
+
+[discrete]
+=== Discrete
+
+==== Too Deep
+
+== Second
+
+[appendix]
+== Appendix
+
+=== Appendix Sub
+";
+        let (doc, _) = parse_document_lossy(source);
+        let numbered = |levels| {
+            doc.numbered_sections(levels)
+                .iter()
+                .map(|section| format!("{} {}", section.label(), section.title.content.text()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(doc.section_numbering(), Some(3));
+        assert_eq!(numbered(2), ["1. First", "1.1. First Sub", "1.2. Second Sub", "2. Second"]);
+        assert_eq!(numbered(1), ["1. First", "2. Second"]);
+
+        let (doc, _) = parse_document_lossy("= Document\n:sectnums:\n:sectnumlevels: 1\n");
+        assert_eq!(doc.section_numbering(), Some(1));
+        let (doc, _) = parse_document_lossy("= Document\n");
+        assert_eq!(doc.section_numbering(), None);
+    }
+
+    #[test]
+    fn test_tags_to_text() {
+        let (_, tags) = parse_inlines::<()>(Span::new("Some *bold _and italic_* text")).unwrap();
+        assert_eq!(tags_to_text(&tags), "Some bold and italic text");
+
+        let link = |content| {
+            Tag::Link(Link { href: Span::new("https://example.org"), content, attributes: vec![] })
+        };
+        let tags = vec![
+            Tag::Text(Span::new("Visit ")),
+            link(Some(vec![Tag::Text(Span::new("Example"))])),
+            Tag::Text(Span::new(" or ")),
+            link(None),
+        ];
+        assert_eq!(tags_to_text(&tags), "Visit Example or https://example.org");
+
+        let mut attribute_list = AttributeList::default();
+        attribute_list.insert("A footnote.", None);
+        let footnote = Macro {
+            name: Span::new("footnote"),
+            target: Span::new(""),
+            attribute_list,
+            positional: vec![Some("A footnote.")],
+        };
+        let tags = vec![Tag::Text(Span::new("Text")), Tag::InlineMacro(footnote)];
+        assert_eq!(tags_to_text(&tags), "Text");
+
+        let (_, tags) = parse_inlines::<()>(Span::new("A ((visible)) term(((hidden)))")).unwrap();
+        assert_eq!(tags_to_text(&tags), "A visible term");
+    }
+
+    #[test]
+    fn test_header_attribute() {
+        let (doc, _) = parse_document_lossy(
+            "= Document\n:foo: bar\n:foo:\n:baz: qux\n:baz!:\n:toc: left\n:toc: right\n",
+        );
+        let header = doc.header.unwrap();
+        let value = |id| header.attribute(id).map(|attr| attr.value.clone());
+        assert_eq!(value("foo"), Some(vec![]));
+        assert_eq!(value("baz"), None);
+        assert_eq!(value("toc"), Some(vec![Span::new("right")]));
+        assert_eq!(value("missing"), None);
+    }
+
+    #[test]
+    fn test_verbatim_tab_expansion() {
+        let source = "----\nif x {\n\treturn;\n}\na\tb\n----\n\n[tabsize=2]\n----\n\tx\n----\n";
+        let (doc, _) = parse_document_lossy(source);
+        let tab_size = doc.tab_size();
+        let content: Vec<_> =
+            doc.content.iter().map(|block| block.verbatim_content(tab_size).unwrap()).collect();
+        assert_eq!(content[0], "if x {\n    return;\n}\na   b\n");
+        assert_eq!(content[1], "  x\n");
+        assert_eq!(doc.content[0].verbatim_content(0).unwrap(), "if x {\n\treturn;\n}\na\tb\n");
+    }
+
+    #[test]
+    fn test_tab_size() {
+        let (doc, _) = parse_document_lossy("= Document\n");
+        assert_eq!(doc.tab_size(), 4);
+        let (doc, _) = parse_document_lossy("= Document\n:tabsize: 8\n");
+        assert_eq!(doc.tab_size(), 8);
+    }
+}
@@ -1,12 +1,64 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 
 #[derive(Clone, Debug, Default)]
 pub struct AttributeMap {
     inner: HashMap<String, String>,
+    /// The attributes that were explicitly unset, like by `:name!:`
+    unset: HashSet<String>,
 }
 
+/// The character replacement attributes every document starts out with
+const INTRINSIC_ATTRIBUTES: &[(&str, &str)] = &[
+    ("blank", ""),
+    ("empty", ""),
+    ("sp", " "),
+    ("nbsp", "&#160;"),
+    ("zwsp", "&#8203;"),
+    ("wj", "&#8288;"),
+    ("apos", "&#39;"),
+    ("quot", "&#34;"),
+    ("lsquo", "&#8216;"),
+    ("rsquo", "&#8217;"),
+    ("ldquo", "&#8220;"),
+    ("rdquo", "&#8221;"),
+    ("deg", "&#176;"),
+    ("plus", "&#43;"),
+    ("brvbar", "&#166;"),
+    ("vbar", "|"),
+    ("amp", "&"),
+    ("lt", "<"),
+    ("gt", ">"),
+    ("startsb", "["),
+    ("endsb", "]"),
+    ("caret", "^"),
+    ("asterisk", "*"),
+    ("tilde", "~"),
+    ("backslash", "\\"),
+    ("backtick", "`"),
+    ("two-colons", "::"),
+    ("two-semicolons", ";;"),
+    ("cpp", "C++"),
+    ("pp", "&#43;&#43;"),
+];
+
+/// Attributes that are set by default in every document
+const DEFAULT_ATTRIBUTES: &[(&str, &str)] = &[
+    ("attribute-missing", "skip"),
+    ("attribute-undefined", "drop-line"),
+    ("idprefix", "_"),
+    ("idseparator", "_"),
+    ("sectids", ""),
+];
+
 impl AttributeMap {
+    /// Creates an attribute map containing the intrinsic character replacement attributes, like
+    /// `{nbsp}`, and the attributes documents have set by default.
+    pub fn with_defaults() -> Self {
+        INTRINSIC_ATTRIBUTES.iter().chain(DEFAULT_ATTRIBUTES).copied().collect()
+    }
+
     #[inline]
     pub fn contains<K>(&self, k: &K) -> bool
     where
@@ -15,6 +67,201 @@ impl AttributeMap {
     {
         self.inner.contains_key(k)
     }
+
+    #[inline]
+    pub fn get<K>(&self, k: &K) -> Option<&str>
+    where
+        String: std::borrow::Borrow<K>,
+        K: std::hash::Hash + Eq + ?Sized,
+    {
+        self.inner.get(k).map(String::as_str)
+    }
+
+    #[inline]
+    pub fn insert(&mut self, k: impl Into<String>, v: impl Into<String>) {
+        let k = k.into();
+        self.unset.remove(&k);
+        self.inner.insert(k, v.into());
+    }
+
+    #[inline]
+    pub fn remove<K>(&mut self, k: &K)
+    where
+        String: std::borrow::Borrow<K>,
+        K: std::hash::Hash + Eq + ?Sized,
+    {
+        self.inner.remove(k);
+    }
+
+    /// Unsets the attribute `k`, like an `:name!:` entry does. Unlike with
+    /// [`remove`](Self::remove), references to it are then handled according to the
+    /// `attribute-undefined` attribute instead of `attribute-missing`.
+    pub fn unset(&mut self, k: impl Into<String>) {
+        let k = k.into();
+        self.inner.remove(&k);
+        self.unset.insert(k);
+    }
+
+    /// Replaces the attribute references in `text`, like `{name}`, with the values of the
+    /// referenced attributes. References to missing attributes are handled according to the
+    /// `attribute-missing` attribute, leaving them as is by default, while references to
+    /// attributes that were explicitly [unset](Self::unset) are handled according to the
+    /// `attribute-undefined` attribute, dropping their line by default.
+    pub fn substitute<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        self.substitute_with(text, |_| None)
+    }
+
+    /// Like [`substitute`](Self::substitute), but consults `resolver` for attributes missing
+    /// from the map. This allows computed attributes like `{docdate}` to be supplied lazily.
+    pub fn substitute_with<'t>(
+        &self,
+        text: &'t str,
+        resolver: impl FnMut(&str) -> Option<String>,
+    ) -> Cow<'t, str> {
+        self.substitute_reporting(text, resolver, |_| ())
+    }
+
+    /// Like [`substitute_with`](Self::substitute_with), but calls `report` with the name of
+    /// every missing attribute whose reference is dropped with a warning, as requested by
+    /// `:attribute-missing: warn`.
+    pub fn substitute_reporting<'t>(
+        &self,
+        text: &'t str,
+        mut resolver: impl FnMut(&str) -> Option<String>,
+        mut report: impl FnMut(&str),
+    ) -> Cow<'t, str> {
+        if !text.contains('{') {
+            return Cow::Borrowed(text);
+        }
+        let missing = self.get("attribute-missing").and_then(AttributeMissing::from_value);
+        let missing = missing.unwrap_or(AttributeMissing::Skip);
+        let undefined = self.get("attribute-undefined").and_then(AttributeUndefined::from_value);
+        let modes = (missing, undefined.unwrap_or(AttributeUndefined::DropLine));
+        let mut substituted = String::with_capacity(text.len());
+        for line in text.split_inclusive('\n') {
+            let len = substituted.len();
+            let keep =
+                self.substitute_line(line, &mut substituted, modes, &mut resolver, &mut report);
+            if !keep {
+                substituted.truncate(len);
+            }
+        }
+        Cow::Owned(substituted)
+    }
+
+    /// Substitutes the attribute references of a single line into `substituted`, returning
+    /// whether the line is to be kept.
+    fn substitute_line(
+        &self,
+        line: &str,
+        substituted: &mut String,
+        (missing, undefined): (AttributeMissing, AttributeUndefined),
+        resolver: &mut impl FnMut(&str) -> Option<String>,
+        report: &mut impl FnMut(&str),
+    ) -> bool {
+        let mut rest = line;
+        while let Some(start) = rest.find('{') {
+            let (before, reference) = rest.split_at(start);
+            let name = reference[1..]
+                .split('}')
+                .next()
+                .filter(|name| reference[1..].len() > name.len() && is_attribute_name(name));
+            let name = match name {
+                Some(name) => name,
+                None => {
+                    substituted.push_str(&rest[..start + 1]);
+                    rest = &rest[start + 1..];
+                    continue;
+                }
+            };
+            let reference_len = name.len() + 2;
+            match before.strip_suffix('\\') {
+                // an escaped reference is kept literally, minus the backslash
+                Some(before) => {
+                    substituted.push_str(before);
+                    substituted.push_str(&reference[..reference_len]);
+                }
+                None => {
+                    substituted.push_str(before);
+                    if self.unset.contains(name) {
+                        match undefined {
+                            AttributeUndefined::Drop => (),
+                            AttributeUndefined::DropLine => return false,
+                        }
+                        rest = &reference[reference_len..];
+                        continue;
+                    }
+                    let value = self
+                        .get(name)
+                        .map(Cow::Borrowed)
+                        .or_else(|| resolver(name).map(Cow::Owned));
+                    match (value, missing) {
+                        (Some(value), _) => substituted.push_str(&value),
+                        (None, AttributeMissing::Skip) => {
+                            substituted.push_str(&reference[..reference_len])
+                        }
+                        (None, AttributeMissing::Drop) => (),
+                        (None, AttributeMissing::DropLine) => return false,
+                        (None, AttributeMissing::Warn) => report(name),
+                    }
+                }
+            }
+            rest = &reference[reference_len..];
+        }
+        substituted.push_str(rest);
+        true
+    }
+}
+
+/// What happens to references to missing attributes, set by the `attribute-missing` attribute
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AttributeMissing {
+    /// `skip`, the reference is left as is
+    Skip,
+    /// `drop`, the reference is removed
+    Drop,
+    /// `drop-line`, the line containing the reference is removed
+    DropLine,
+    /// `warn`, the reference is removed and reported
+    Warn,
+}
+
+impl AttributeMissing {
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "skip" => Some(AttributeMissing::Skip),
+            "drop" => Some(AttributeMissing::Drop),
+            "drop-line" => Some(AttributeMissing::DropLine),
+            "warn" => Some(AttributeMissing::Warn),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to references to explicitly unset attributes, set by the `attribute-undefined`
+/// attribute
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AttributeUndefined {
+    /// `drop`, the reference is removed
+    Drop,
+    /// `drop-line`, the line containing the reference is removed
+    DropLine,
+}
+
+impl AttributeUndefined {
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "drop" => Some(AttributeUndefined::Drop),
+            "drop-line" => Some(AttributeUndefined::DropLine),
+            _ => None,
+        }
+    }
+}
+
+fn is_attribute_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_alphanumeric() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-')
 }
 
 impl<T, U> FromIterator<(T, U)> for AttributeMap
@@ -25,6 +272,89 @@ where
     fn from_iter<II: IntoIterator<Item = (T, U)>>(iter: II) -> Self {
         AttributeMap {
             inner: FromIterator::from_iter(iter.into_iter().map(|(t, u)| (t.into(), u.into()))),
+            unset: HashSet::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_with_defaults() {
+        let attributes = AttributeMap::with_defaults();
+        assert_eq!(attributes.get("sp"), Some(" "));
+        assert_eq!(attributes.get("vbar"), Some("|"));
+        assert_eq!(attributes.get("nbsp"), Some("&#160;"));
+        assert!(!AttributeMap::default().contains("sp"));
+    }
+
+    #[test]
+    fn test_substitute() {
+        let mut attributes = AttributeMap::with_defaults();
+        attributes.insert("author", "John Doe");
+        assert_eq!(
+            attributes.substitute("{author}{sp}wrote \\{author} in {unknown} {not an attribute}"),
+            "John Doe wrote {author} in {unknown} {not an attribute}"
+        );
+    }
+
+    #[test]
+    fn test_substitute_with_resolver() {
+        let attributes = AttributeMap::with_defaults();
+        let resolver = |name: &str| match name {
+            "docdate" => Some("2020-07-31".to_owned()),
+            _ => None,
+        };
+        assert_eq!(
+            attributes.substitute_with("Last updated{sp}{docdate}, see {docfile}", resolver),
+            "Last updated 2020-07-31, see {docfile}"
+        );
+    }
+
+    #[test]
+    fn test_attribute_missing() {
+        let text = "Written by {author}.\nThe end.\n";
+        let substitute = |mode: &str| {
+            let mut attributes = AttributeMap::with_defaults();
+            attributes.insert("attribute-missing", mode);
+            let mut missing = Vec::new();
+            let substituted = attributes
+                .substitute_reporting(text, |_| None, |name| missing.push(name.to_owned()))
+                .into_owned();
+            (substituted, missing)
+        };
+        assert_eq!(substitute("skip"), (text.to_owned(), vec![]));
+        assert_eq!(substitute("drop"), ("Written by .\nThe end.\n".to_owned(), vec![]));
+        assert_eq!(substitute("drop-line"), ("The end.\n".to_owned(), vec![]));
+        assert_eq!(
+            substitute("warn"),
+            ("Written by .\nThe end.\n".to_owned(), vec!["author".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_attribute_undefined_drop_line() {
+        let mut attributes = AttributeMap::with_defaults();
+        attributes.unset("author");
+        // references to unset attributes drop their line by default, missing ones are kept
+        assert_eq!(
+            attributes.substitute("Written by {author}.\nSee {unknown}.\n"),
+            "See {unknown}.\n"
+        );
+        attributes.insert("author", "John Doe");
+        assert_eq!(attributes.substitute("Written by {author}.\n"), "Written by John Doe.\n");
+    }
+
+    #[test]
+    fn test_attribute_undefined_drop() {
+        let mut attributes = AttributeMap::with_defaults();
+        attributes.insert("attribute-undefined", "drop");
+        attributes.unset("author");
+        assert_eq!(
+            attributes.substitute("Written by {author}.\nSee {unknown}.\n"),
+            "Written by .\nSee {unknown}.\n"
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Decoding of sources that aren't encoded as UTF-8.
+
+use std::borrow::Cow;
+
+/// The character encoding of a source
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Invalid sequences are replaced with `U+FFFD`
+    #[default]
+    Utf8,
+    /// ISO-8859-1, every byte maps to the code point of the same value
+    Latin1,
+    /// Latin-1 with printable characters in place of most of the C1 control codes
+    Windows1252,
+}
+
+/// The characters Windows-1252 assigns to the bytes `0x80..=0x9F`. The five bytes it leaves
+/// undefined are mapped to the C1 control codes of the same value, as browsers do.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+impl Encoding {
+    /// Decodes `bytes`, which never fails as every byte sequence has a decoding.
+    pub fn decode(self, bytes: &[u8]) -> Cow<'_, str> {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes),
+            Encoding::Latin1 => bytes.iter().map(|&b| char::from(b)).collect(),
+            Encoding::Windows1252 => bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(b - 0x80)],
+                    _ => char::from(b),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Encoding;
+
+    #[test]
+    fn test_decode() {
+        let bytes = b"Caf\xe9 \x80 \x93quoted\x94";
+        assert_eq!(Encoding::Latin1.decode(bytes), "Café \u{80} \u{93}quoted\u{94}");
+        assert_eq!(Encoding::Windows1252.decode(bytes), "Café € “quoted”");
+        assert_eq!(Encoding::Utf8.decode(bytes), "Caf\u{FFFD} \u{FFFD} \u{FFFD}quoted\u{FFFD}");
+        assert_eq!(Encoding::Utf8.decode("Café".as_bytes()), "Café");
+    }
+}
@@ -0,0 +1,39 @@
+use std::ops::Range;
+
+use crate::ast::Doctype;
+use crate::source::PreprocessError;
+
+/// A diagnostic produced while parsing a document
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ParseError {
+    /// A region of the input that could not be parsed as any block
+    InvalidBlock {
+        /// The line the region starts on, 1-based
+        line: u32,
+        /// The byte range of the region within the input
+        range: Range<usize>,
+    },
+    /// The document has no header, but [`ParseOptions::require_header`] is set
+    ///
+    /// [`ParseOptions::require_header`]: crate::parser::ParseOptions::require_header
+    MissingHeader,
+    /// The document has no title, but its doctype requires one
+    MissingDocumentTitle { doctype: Doctype },
+    /// Preprocessing the source failed. Includes can't be resolved when parsing a string
+    /// directly, which requires a [`DocumentSource`](crate::source::DocumentSource) instead.
+    Preprocess(PreprocessError<()>),
+}
+
+/// A section nested more than one level deeper than its parent, like a `====` section directly
+/// within a `==` one, see [`validate_section_levels`](crate::ast::validate_section_levels).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SectionLevelWarning {
+    /// The line of the section title, 1-based
+    pub line: u32,
+    /// The byte range of the section title's text within the input
+    pub range: Range<usize>,
+    /// The level the section was expected to have at most
+    pub expected: usize,
+    /// The actual level of the section
+    pub found: usize,
+}
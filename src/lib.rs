@@ -1,6 +1,11 @@
 pub mod ast;
-mod attributes;
+pub mod attributes;
+#[cfg(feature = "encoding")]
+pub mod encoding;
+pub mod error;
 pub mod parser;
+pub mod render;
+pub mod source;
 mod span;
 
 use self::span::Span;
@@ -1,55 +1,304 @@
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_until, take_while1};
-use nom::character::complete::{alphanumeric1, digit1, newline, none_of};
-use nom::combinator::{all_consuming, map, map_opt, opt, recognize};
-use nom::error::ParseError;
-use nom::multi::{fold_many_m_n, many0, many1, many_till};
+use nom::bytes::complete::{tag, take_till, take_till1, take_until, take_while1};
+use nom::character::complete::{alpha1, alphanumeric1, digit1, newline, none_of};
+use nom::combinator::{all_consuming, map, map_opt, not, opt, peek, recognize, verify};
+use nom::error::{ErrorKind, ParseError};
+use nom::multi::{fold_many_m_n, many0, many1};
 use nom::sequence::{delimited, pair, preceded, terminated, tuple};
-use nom::Slice;
+use nom::{Offset, Slice};
+
+use std::borrow::Cow;
 
 use crate::ast::*;
+use crate::error;
 use crate::Span;
 
+mod inline;
+mod list;
 mod nom_ext;
+mod table;
+pub use self::inline::*;
+pub use self::list::*;
 use self::nom_ext::*;
+pub use self::table::*;
 
 #[cfg(test)]
 mod tests;
 
 type PResult<'a, T, E> = nom::IResult<Span<'a>, T, E>;
 
+/// Options controlling how a document is parsed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Whether comments are kept as [`Context::Comment`] blocks instead of being dropped
+    pub preserve_comments: bool,
+    /// Whether parsing fails if the document has no header
+    pub require_header: bool,
+    /// Whether a leading `= Title` is parsed as a level 0 section instead of a document header,
+    /// as is wanted for document fragments
+    pub forbid_header: bool,
+    /// Whether parsing stops after the header, leaving the document without content
+    pub header_only: bool,
+    /// The doctype to assume if the document doesn't set one in its header
+    pub doctype: Option<Doctype>,
+}
+
+impl ParseOptions {
+    /// Resolves the doctype of a document with the given header, which may set it via the
+    /// `doctype` attribute.
+    fn resolve_doctype(&self, header: Option<&DocumentHeader<'_>>) -> Doctype {
+        header.and_then(DocumentHeader::doctype).or(self.doctype).unwrap_or_default()
+    }
+}
+
 pub fn parse_doc<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Document<'a>, E> {
-    let (i, header) = opt(parse_doc_header)(i)?;
+    parse_doc_with(i, ParseOptions::default())
+}
+
+/// Like [`parse_doc`], but with the given options.
+pub fn parse_doc_with<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+    options: ParseOptions,
+) -> PResult<'a, Document<'a>, E> {
+    let (i, header) = parse_header_with(i, options)?;
+    let title_required = options.resolve_doctype(header.as_ref()).requires_title();
+    if (options.require_header || title_required) && header.is_none() {
+        return Err(nom::Err::Error(E::from_error_kind(i, ErrorKind::Verify)));
+    }
+    if options.header_only {
+        return Ok((i, Document { header, content: Vec::new() }));
+    }
 
     let f = terminated(parse_blocks, wsnl);
     let mut f = all_consuming(f);
-    let (i, contents) = f(i)?;
+    let (i, mut contents) = f(i)?;
+    if !options.preserve_comments {
+        strip_comments(&mut contents);
+    }
 
     let doc = Document { header, content: contents };
     Ok((i, doc))
 }
 
+/// Parses the document header, unless it is forbidden by `options`.
+fn parse_header_with<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+    options: ParseOptions,
+) -> PResult<'a, Option<DocumentHeader<'a>>, E> {
+    if options.forbid_header {
+        return Ok((i, None));
+    }
+    opt(parse_doc_header)(i)
+}
+
+/// Parses a document without bailing on the first error.
+///
+/// Regions that can't be parsed as a block are skipped up to the next blank line and inserted
+/// into the document as [`Context::Error`] blocks, with a diagnostic being recorded for each.
+pub fn parse_document_lossy(i: &str) -> (Document<'_>, Vec<error::ParseError>) {
+    parse_document_with(i, ParseOptions::default())
+}
+
+/// Like [`parse_document_lossy`], but with the given options.
+pub fn parse_document_with(
+    i: &str,
+    options: ParseOptions,
+) -> (Document<'_>, Vec<error::ParseError>) {
+    let input = Span::new(i);
+    let (i, header) = parse_header_with::<()>(input, options).unwrap_or((input, None));
+
+    let mut content = Vec::new();
+    let mut errors = Vec::new();
+    if header.is_none() {
+        if options.require_header {
+            errors.push(error::ParseError::MissingHeader);
+        }
+        let doctype = options.resolve_doctype(None);
+        if doctype.requires_title() {
+            errors.push(error::ParseError::MissingDocumentTitle { doctype });
+        }
+    }
+    if options.header_only {
+        return (Document { header, content }, errors);
+    }
+    let hardbreaks = header.as_ref().is_some_and(|it| it.attribute("hardbreaks-option").is_some());
+    for block in BlockParser::with_options(i, options) {
+        match block {
+            Ok(mut block) => {
+                if hardbreaks {
+                    set_hardbreaks(&mut block);
+                }
+                content.push(block)
+            }
+            Err(err) => {
+                if let error::ParseError::InvalidBlock { range, .. } = &err {
+                    content.push(Block::new(Context::Error(input.slice(range.clone()))));
+                }
+                errors.push(err);
+            }
+        }
+    }
+
+    (Document { header, content }, errors)
+}
+
+/// An iterator that lazily parses the top-level blocks of its input.
+///
+/// Regions that can't be parsed as a block are skipped up to the next blank line, yielding an
+/// error for each.
+pub struct BlockParser<'a> {
+    i: Span<'a>,
+    options: ParseOptions,
+}
+
+impl<'a> BlockParser<'a> {
+    pub fn new(i: Span<'a>) -> Self {
+        Self::with_options(i, ParseOptions::default())
+    }
+
+    pub fn with_options(i: Span<'a>, options: ParseOptions) -> Self {
+        BlockParser { i, options }
+    }
+}
+
+impl<'a> Iterator for BlockParser<'a> {
+    type Item = Result<Block<'a>, error::ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if all_consuming(wsnl::<()>)(self.i).is_ok() {
+                return None;
+            }
+            let (rest, mut block) = match bounded(parse_attributed_block::<()>)(self.i) {
+                Ok(res) => res,
+                Err(_) => break,
+            };
+            self.i = rest;
+            if !self.options.preserve_comments {
+                if let Context::Comment(_) = block.context {
+                    continue;
+                }
+                strip_nested_comments(&mut block);
+            }
+            return Some(Ok(block));
+        }
+        let mut skip_erroneous =
+            preceded(many0(ws_with_nl::<()>), recognize(many1(take_nonblank_line)));
+        // fails if only trailing whitespace without a newline is left
+        let (rest, span) = skip_erroneous(self.i).ok()?;
+        self.i = rest;
+        let offset = span.location_offset();
+        Some(Err(error::ParseError::InvalidBlock {
+            line: span.location_line(),
+            range: offset..offset + span.len(),
+        }))
+    }
+}
+
+/// Removes all comment blocks from `blocks`, including those nested in other blocks.
+fn strip_comments(blocks: &mut Vec<Block<'_>>) {
+    blocks.retain(|block| !matches!(block.context, Context::Comment(_)));
+    blocks.iter_mut().for_each(strip_nested_comments);
+}
+
+fn strip_nested_comments(block: &mut Block<'_>) {
+    match &mut block.context {
+        Context::SectionTitle(_, blocks)
+        | Context::Admonition { blocks, .. }
+        | Context::Example(blocks)
+        | Context::Sidebar(blocks)
+        | Context::Quote(blocks)
+        | Context::Open(blocks) => strip_comments(blocks),
+        Context::List(list) => {
+            list.items.iter_mut().for_each(|item| strip_comments(&mut item.blocks))
+        }
+        _ => (),
+    }
+}
+
+/// Sets the `hardbreaks` option on `block` if it is a paragraph, or on the paragraphs nested in
+/// it, as the `hardbreaks-option` document attribute does.
+fn set_hardbreaks(block: &mut Block<'_>) {
+    if let Context::Paragraph(_) = block.context {
+        if !block.has_option("hardbreaks") {
+            block.options.push(Span::new("hardbreaks"));
+        }
+        return;
+    }
+    match &mut block.context {
+        Context::SectionTitle(_, blocks)
+        | Context::Admonition { blocks, .. }
+        | Context::Example(blocks)
+        | Context::Sidebar(blocks)
+        | Context::Quote(blocks)
+        | Context::Open(blocks) => blocks.iter_mut().for_each(set_hardbreaks),
+        Context::List(list) => {
+            list.items.iter_mut().flat_map(|item| &mut item.blocks).for_each(set_hardbreaks)
+        }
+        _ => (),
+    }
+}
+
 pub fn parse_doc_header<'a, E: ParseError<Span<'a>>>(
     i: Span<'a>,
 ) -> PResult<'a, DocumentHeader<'a>, E> {
-    let (i, title) = preceded(tag("= "), terminated(take_until("\n"), tag("\n")))(i)?;
-    // parse author
-    // parse version
+    let (i, title) =
+        preceded(pair(many0(ws_with_nl), tag("= ")), terminated(take_until("\n"), tag("\n")))(i)?;
+    let (i, author) = opt(parse_author_line)(i)?;
+    // a revision line may only follow an author line
+    let (i, version) = match author {
+        Some(_) => opt(parse_revision_line)(i)?,
+        None => (i, None),
+    };
     let (i, attributes) = many0(parse_doc_attribute)(i)?;
-    let h = DocumentHeader { title, author: None, version: None, attributes };
+    let h = DocumentHeader { title, author, version, attributes };
     Ok((i, h))
 }
 
+/// Takes a line of the header that isn't an attribute entry or a comment.
+fn header_line<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
+    verify(take_nonblank_line, |line: &Span<'a>| !line.starts_with(':') && !line.starts_with("//"))(
+        i,
+    )
+}
+
+/// Parses the author line following the document title, like `Jane Doe <jane@example.org>`,
+/// into the name of its first author, as further authors may follow separated by `;`.
+pub fn parse_author_line<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Author<'a>, E> {
+    let (i, line) = header_line(i)?;
+    let name = line.slice(..line.find(&['<', ';'][..]).unwrap_or(line.len()));
+    Ok((i, Author { full_name: name.trim() }))
+}
+
+/// Parses the revision line following the author line, like `v1.0, 2021-01-31: A remark`. Either
+/// the version or the date may be left out, leaving its span empty, while the remark is dropped.
+pub fn parse_revision_line<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> PResult<'a, Version<'a>, E> {
+    let (i, line) = header_line(i)?;
+    let line = line.slice(..line.find(": ").unwrap_or(line.len())).trim();
+    let version = match line.find(',') {
+        Some(idx) => {
+            Version { version: line.slice(..idx).trim(), date: line.slice(idx + 1..).trim() }
+        }
+        None if line.starts_with('v') => Version { version: line, date: line.slice(line.len()..) },
+        None => Version { version: line.slice(..0), date: line },
+    };
+    Ok((i, version))
+}
+
 pub fn parse_doc_attribute<'a, E: ParseError<Span<'a>>>(
     i: Span<'a>,
 ) -> PResult<'a, DocAttribute<'a>, E> {
     // FIXME: hard/soft wrap attribute values
     let ctor = |((bang1, id), value): ((Option<_>, Span<'a>), Option<_>)| {
         let ends_with_bang = id.text().ends_with('!');
+        let unset = bang1.is_some() || ends_with_bang;
         DocAttribute {
             id: if ends_with_bang { id.slice(..id.len() - 1) } else { id },
-            unset: bang1.is_some() || ends_with_bang,
-            value: value.into_iter().collect(),
+            unset,
+            // the value of an unset attribute is irrelevant
+            value: if unset { Vec::new() } else { value.into_iter().collect() },
         }
     };
     let id =
@@ -57,60 +306,385 @@ pub fn parse_doc_attribute<'a, E: ParseError<Span<'a>>>(
     map(terminated(pair(id, opt(preceded(ws1, take_until("\n")))), ws_with_nl), ctor)(i)
 }
 
+/// Parses a single attribute of an attribute list, e.g. `positional value`, `name=value` or
+/// `name="value"`.
+/// A `]` may appear in a quoted value, or in an unquoted one when escaped as `\]`, in which case
+/// the backslash is kept in the value. Quotes followed by more text, like in `"14"abc`, are taken
+/// as part of an unquoted value.
 pub fn parse_attribute<'a, E: ParseError<Span<'a>>>(
     i: Span<'a>,
 ) -> PResult<'a, (Span<'a>, Option<Span<'a>>), E> {
     let name = recognize(pair(alphanumeric1, many0(alt((alphanumeric1, tag("-"), tag("."))))));
-    pair(name, opt(preceded(ws_delimited(tag("=")), recognize(many1(none_of(",]\n"))))))(i)
+    let value = || {
+        let value_end = peek(preceded(ws, alt((tag(","), tag("]"), newline_or_eof))));
+        let quoted_value =
+            terminated(delimited(tag("\""), take_till(|c| c == '"'), tag("\"")), value_end);
+        alt((quoted_value, recognize(many1(alt((tag("\\]"), recognize(none_of(",]\n"))))))))
+    };
+    let named = pair(name, map(preceded(ws_delimited(tag("=")), value()), Some));
+    // positional attributes are stored as names without a value
+    let positional = map(value(), |value: Span<'a>| (value.trim_end(), None));
+    alt((named, positional))(i)
+}
+
+/// Parses a single shorthand attribute, e.g. `#intro`, `.lead` or `%header`.
+pub fn parse_shorthand_attribute<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> PResult<'a, Attribute<'a>, E> {
+    let name = || take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_');
+    alt((
+        map(preceded(tag("#"), name()), Attribute::Id),
+        map(preceded(tag("."), name()), Attribute::Role),
+        map(preceded(tag("%"), name()), Attribute::Option),
+    ))(i)
 }
 
 pub fn parse_attribute_list<'a, E: ParseError<Span<'a>>>(
     i: Span<'a>,
 ) -> PResult<'a, AttributeList<'a>, E> {
-    let parse_attributes = |mut i| {
-        let mut attr_list = AttributeList::default();
-        if let Ok((i2, (key, val))) = parse_attribute::<()>(i) {
-            attr_list.insert(key.text(), val.map(|s| s.text()));
-            i = i2;
-        }
-        while let PResult::<_, ()>::Ok((i2, (key, val))) =
-            preceded(ws_delimited(tag(",")), parse_attribute)(i)
-        {
-            i = i2;
-            attr_list.insert(key.text(), val.map(|s| s.text()));
+    delimited(tag("["), parse_attributes, tag("]"))(i)
+}
+
+/// Parses the attribute list of a macro, additionally returning its positional attributes by
+/// their position among all entries as Asciidoctor counts them, see [`Macro::positional`]. Unlike
+/// in other attribute lists, entries may be empty, like the first one in `[,200,100]`.
+pub fn parse_macro_attribute_list<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> PResult<'a, (AttributeList<'a>, Vec<Option<&'a str>>), E> {
+    let (mut i, _) = tag("[")(i)?;
+    let mut attr_list = AttributeList::default();
+    let mut positional = Vec::new();
+    loop {
+        match parse_attribute::<()>(i) {
+            Ok((rest, (key, val))) => {
+                attr_list.insert(key.text(), val.map(|s| s.text()));
+                positional.push(if val.is_none() { Some(key.text()) } else { None });
+                i = rest;
+            }
+            Err(_) => positional.push(None),
+        }
+        match ws_delimited(tag::<_, _, ()>(","))(i) {
+            Ok((rest, _)) => i = rest,
+            Err(_) => break,
         }
-        Ok((i, attr_list))
+    }
+    let (i, _) = tag("]")(i)?;
+    // an empty attribute list has no entries rather than a single empty one
+    if attr_list.is_empty() && positional == [None] {
+        positional.clear();
+    }
+    Ok((i, (attr_list, positional)))
+}
+
+/// Parses the attribute list of a block, whose first positional attribute may be written in the
+/// shorthand form `style#id.role%option`.
+pub fn parse_block_attribute_list<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> PResult<'a, (AttributeList<'a>, Vec<Attribute<'a>>), E> {
+    let style = recognize(pair(alphanumeric1, many0(alt((alphanumeric1, tag("-"))))));
+    let shorthand = pair(opt(style), many1(parse_shorthand_attribute));
+
+    let (i, _) = tag("[")(i)?;
+    let (i, attributes) = match opt(shorthand)(i)? {
+        (i, Some((style, shorthands))) => {
+            let mut attr_list = AttributeList::default();
+            if let Some(style) = style {
+                attr_list.insert(style.text(), None);
+            }
+            (parse_trailing_attributes(i, &mut attr_list), (attr_list, shorthands))
+        }
+        (i, None) => map(parse_attributes, |attr_list| (attr_list, Vec::new()))(i)?,
     };
-    delimited(tag("["), parse_attributes, tag("]"))(i)
+    let (i, _) = tag("]")(i)?;
+    Ok((i, attributes))
+}
+
+fn parse_attributes<'a, E: ParseError<Span<'a>>>(
+    mut i: Span<'a>,
+) -> PResult<'a, AttributeList<'a>, E> {
+    let mut attr_list = AttributeList::default();
+    if let Ok((i2, (key, val))) = parse_attribute::<()>(i) {
+        attr_list.insert(key.text(), val.map(|s| s.text()));
+        i = i2;
+    }
+    Ok((parse_trailing_attributes(i, &mut attr_list), attr_list))
+}
+
+fn parse_trailing_attributes<'a>(mut i: Span<'a>, attr_list: &mut AttributeList<'a>) -> Span<'a> {
+    while let PResult::<_, ()>::Ok((i2, (key, val))) =
+        preceded(ws_delimited(tag(",")), parse_attribute)(i)
+    {
+        i = i2;
+        attr_list.insert(key.text(), val.map(|s| s.text()));
+    }
+    i
 }
 
 pub fn parse_blocks<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Blocks<'a>, E> {
-    many0(parse_attributed_block)(i)
+    many0(bounded(parse_attributed_block))(i)
 }
 
 pub fn parse_attributed_block<'a, E: ParseError<Span<'a>>>(
     i: Span<'a>,
 ) -> PResult<'a, Block<'a>, E> {
-    let thematic_break = map(tag("'''"), |_| Context::ThematicBreak);
-    let page_break = map(tag(">>>"), |_| Context::PageBreak);
-    let fenced = map(delimited(pair(tag("```"), newline), take_until("```"), tag("```")), |span| {
-        Context::Listing(span)
-    });
+    // breaks have to make up the whole line, trailing whitespace aside, as otherwise the line
+    // would never be tried as the start of a paragraph
+    let break_line = |marker| terminated(tag(marker), pair(ws, peek(newline_or_eof)));
+    let thematic_break = map(break_line("'''"), |_| Context::ThematicBreak);
+    let page_break = map(break_line(">>>"), |_| Context::PageBreak);
     let block_macro = map(parse_block_macro, Context::BlockMacro);
+    let list = map(bounded(parse_list), Context::List);
+    let literal = map(parse_literal_paragraph, Context::Literal);
+    let comment = map(parse_comment, Context::Comment);
+    let paragraph = map(parse_paragraph, Context::Paragraph);
 
-    let parse_block =
-        terminated(alt((thematic_break, page_break, fenced, block_macro)), newline_or_eof);
-    preceded(
-        many0(ws_with_nl),
-        map(
-            tuple((opt(parse_attribute_list), parse_block, parse_callouts)),
-            |(attr_list, context, callouts)| Block {
-                context,
-                attributes: attr_list.unwrap_or_default(),
-                callouts,
-            },
+    let (metadata, _) = many0(ws_with_nl)(i)?;
+    let (i, BlockMetadata { title, attributes, mut id, roles, options }) =
+        parse_block_metadata(metadata)?;
+
+    let section = map(
+        |i| parse_section(i, &attributes),
+        |(title, blocks)| Context::SectionTitle(title, blocks),
+    );
+    let table = map(bounded(|i| parse_table(i, &attributes, &options)), Context::Table);
+    let passthrough = map(parse_passthrough_block, Context::Passthrough);
+    let is_verse = matches!(attributes.get_index(0), Some((&"verse", None)));
+    let verse = map(verify(parse_verse_block, |_| is_verse), |content| {
+        Context::Verse(Verse::new(content, &attributes, metadata))
+    });
+    let (i, mut context) = alt((
+        section,
+        verse,
+        parse_compound_block,
+        parse_verbatim_block,
+        passthrough,
+        terminated(
+            alt((
+                thematic_break,
+                page_break,
+                comment,
+                table,
+                block_macro,
+                list,
+                literal,
+                paragraph,
+            )),
+            newline_or_eof,
         ),
-    )(i)
+    ))(i)?;
+    let (i, callouts) = parse_callouts(i)?;
+
+    match &mut context {
+        Context::SectionTitle(section, _) => {
+            if let Some((content, anchor)) = split_trailing_anchor(section.content) {
+                section.content = content;
+                id = id.or(Some(anchor));
+            }
+        }
+        Context::List(list) => {
+            list.style = ListStyle::from_attributes(&attributes);
+            list.start = attributes
+                .get("start")
+                .copied()
+                .flatten()
+                .and_then(|start| start.trim().parse().ok());
+        }
+        Context::Example(blocks) => {
+            // an admonition style turns an example block into an admonition block
+            if let Some(label) = admonition_style(&attributes).and_then(|it| metadata.sub_span(it))
+            {
+                let blocks = std::mem::take(blocks);
+                context = Context::Admonition { label, blocks };
+            }
+        }
+        Context::Paragraph(paragraph) => {
+            // on a paragraph, it makes the paragraph the sole block of an admonition
+            if let Some(label) = admonition_style(&attributes).and_then(|it| metadata.sub_span(it))
+            {
+                let blocks = vec![Block::new(Context::Paragraph(*paragraph))];
+                context = Context::Admonition { label, blocks };
+            } else if is_verse {
+                context = Context::Verse(Verse::new(*paragraph, &attributes, metadata));
+            }
+        }
+        Context::Passthrough(content) => {
+            if let Some((&style, None)) = attributes.get_index(0) {
+                if let Some(notation) = StemNotation::from_style(style) {
+                    context = Context::Stem { notation, content: *content };
+                }
+            }
+        }
+        _ => (),
+    }
+    Ok((i, Block { title, attributes, id, roles, options, callouts, ..Block::new(context) }))
+}
+
+/// The metadata lines preceding a block, merged in the order they appear
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BlockMetadata<'a> {
+    pub title: Option<Span<'a>>,
+    pub attributes: AttributeList<'a>,
+    pub id: Option<Span<'a>>,
+    pub roles: Vec<Span<'a>>,
+    pub options: Vec<Span<'a>>,
+}
+
+/// Parses the metadata lines of a block, that is any number of anchor (`[[id]]`), title
+/// (`.Title`) and attribute list (`[style#id.role]`) lines in any order.
+///
+/// Later lines take precedence over earlier ones for the title, the id and named attributes.
+pub fn parse_block_metadata<'a, E: ParseError<Span<'a>>>(
+    mut i: Span<'a>,
+) -> PResult<'a, BlockMetadata<'a>, E> {
+    let mut metadata = BlockMetadata::default();
+    let mut anchor_reftext = None;
+    loop {
+        if let Ok((rest, (id, reftext))) = terminated(parse_block_anchor, ws_with_nl::<()>)(i) {
+            metadata.id = Some(id);
+            anchor_reftext = reftext.or(anchor_reftext);
+            i = rest;
+        } else if let Ok((rest, title)) = parse_block_title::<()>(i) {
+            metadata.title = Some(title);
+            i = rest;
+        } else if let Ok((rest, (attributes, shorthands))) =
+            terminated(parse_block_attribute_list, ws_with_nl::<()>)(i)
+        {
+            metadata.attributes.extend(attributes);
+            for shorthand in shorthands {
+                match shorthand {
+                    Attribute::Id(id) => metadata.id = Some(id),
+                    Attribute::Role(role) => metadata.roles.push(role),
+                    Attribute::Option(option) => metadata.options.push(option),
+                }
+            }
+            i = rest;
+        } else {
+            // inserted last to keep the style as the first attribute
+            if let Some(reftext) = anchor_reftext {
+                metadata.attributes.insert("reftext", Some(reftext.text()));
+            }
+            return Ok((i, metadata));
+        }
+    }
+}
+
+/// Parses a block anchor, like `[[intro]]` or `[[intro,Introduction]]`, into its id and reftext.
+pub fn parse_block_anchor<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> PResult<'a, (Span<'a>, Option<Span<'a>>), E> {
+    let id = take_while1(|c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '.'));
+    let reftext = preceded(tag(","), take_till1(|c| c == ']' || c == '\n'));
+    delimited(tag("[["), pair(id, opt(reftext)), tag("]]"))(i)
+}
+
+/// Parses a block title line, like `.My Listing`.
+pub fn parse_block_title<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
+    let title = verify(take_till(|c| c == '\n'), |title: &Span| {
+        !title.is_empty() && !title.starts_with(&['.', ' ', '\t'][..])
+    });
+    delimited(tag("."), title, newline)(i)
+}
+
+/// Parses a line comment, returning the text following the `//`, or a comment block delimited by
+/// `////`, returning its content. Like in Asciidoctor, a line starting with more than two slashes
+/// is not a line comment.
+pub fn parse_comment<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
+    let delimiter = || verify(take_while1(|c| c == '/'), |it: &Span| it.len() >= 4);
+    let line_end = || pair(ws, peek(newline_or_eof));
+    let block = delimited(
+        pair(delimiter(), pair(ws, newline)),
+        recognize(many0(preceded(
+            not(pair(delimiter(), line_end())),
+            terminated(take_till(|c| c == '\n'), newline),
+        ))),
+        pair(delimiter(), line_end()),
+    );
+    let line = preceded(pair(tag("//"), not(tag("/"))), take_till(|c| c == '\n'));
+    alt((block, line))(i)
+}
+
+const ADMONITION_LABELS: &[&str] = &["NOTE", "TIP", "IMPORTANT", "CAUTION", "WARNING"];
+
+/// Returns the admonition label if the block style, the first positional attribute, is one.
+fn admonition_style<'a>(attributes: &AttributeList<'a>) -> Option<&'a str> {
+    match attributes.get_index(0) {
+        Some((&label, None)) if ADMONITION_LABELS.contains(&label) => Some(label),
+        _ => None,
+    }
+}
+
+/// Parses a delimited block whose content consists of blocks, like an example block delimited by
+/// `====`, a sidebar delimited by `****` or an open block delimited by `--`.
+pub fn parse_compound_block<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> PResult<'a, Context<'a>, E> {
+    let (rest, (delimiter, content)) =
+        parse_delimited(alt((fence('='), fence('*'), fence('_'), tag("--"))))(i)?;
+    let (_, blocks) = all_consuming(terminated(parse_blocks, wsnl))(content)?;
+
+    let context = match delimiter.text().as_bytes()[0] {
+        b'=' => Context::Example(blocks),
+        b'*' => Context::Sidebar(blocks),
+        b'_' => Context::Quote(blocks),
+        _ => Context::Open(blocks),
+    };
+    Ok((rest, context))
+}
+
+/// Parses a verbatim block, that is a listing block delimited by `----` or a fenced code block
+/// delimited by three or more backticks, or a literal block delimited by `....`.
+pub fn parse_verbatim_block<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> PResult<'a, Context<'a>, E> {
+    let backticks = verify(take_while1(|c| c == '`'), |fence: &Span| fence.len() >= 3);
+    let (rest, (delimiter, content)) =
+        parse_delimited(alt((fence('-'), fence('.'), backticks)))(i)?;
+    let context = match delimiter.text().as_bytes()[0] {
+        b'.' => Context::Literal(content),
+        _ => Context::Listing(content),
+    };
+    Ok((rest, context))
+}
+
+/// Parses the content of a verse block delimited by `____`, keeping its line breaks intact.
+pub fn parse_verse_block<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
+    map(parse_delimited(fence('_')), |(_, content)| {
+        content.slice(..content.trim_end_matches('\n').len())
+    })(i)
+}
+
+/// Parses a passthrough block delimited by `++++`, whose content is kept verbatim.
+pub fn parse_passthrough_block<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> PResult<'a, Span<'a>, E> {
+    map(parse_delimited(fence('+')), |(_, content)| content)(i)
+}
+
+/// Recognizes a delimiter line consisting of at least four `c`s.
+fn fence<'a, E: ParseError<Span<'a>>>(c: char) -> impl FnMut(Span<'a>) -> PResult<'a, Span<'a>, E> {
+    verify(take_while1(move |it| it == c), |fence: &Span| fence.len() >= 4)
+}
+
+/// Parses a delimited block opened by a line recognized by `delimiter`, returning the delimiter
+/// and the content up until the closing delimiter line, which has to match the opening one.
+fn parse_delimited<'a, E: ParseError<Span<'a>>>(
+    delimiter: impl FnMut(Span<'a>) -> PResult<'a, Span<'a>, E>,
+) -> impl FnMut(Span<'a>) -> PResult<'a, (Span<'a>, Span<'a>), E> {
+    let mut delimiter = terminated(delimiter, newline);
+    move |i| {
+        let (content, delimiter) = delimiter(i)?;
+        let mut rest = content;
+        loop {
+            if rest.is_empty() {
+                return Err(nom::Err::Error(E::from_error_kind(rest, ErrorKind::TakeUntil)));
+            }
+            let (next, line) = terminated(take_till(|c| c == '\n'), newline_or_eof)(rest)?;
+            if line.trim_end() == delimiter {
+                return Ok((next, (delimiter, content.slice(..content.offset(&rest)))));
+            }
+            rest = next;
+        }
+    }
 }
 
 pub fn parse_callouts<'a, E: ParseError<Span<'a>>>(
@@ -135,9 +709,14 @@ pub fn parse_block_macro<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a
             recognize(take_while1(|c: char| c.is_ascii_alphabetic())),
             tag("::"),
             take_while1(|c| c != '[' && c != '\n'),
-            parse_attribute_list,
+            parse_macro_attribute_list,
         )),
-        |(name, _, target, attribute_list)| Macro { name, target, attribute_list },
+        |(name, _, target, (attribute_list, positional))| Macro {
+            name,
+            target,
+            attribute_list,
+            positional,
+        },
     )(i)
 }
 
@@ -145,15 +724,187 @@ pub fn parse_section_title<'a, E: ParseError<Span<'a>>>(
     i: Span<'a>,
 ) -> PResult<'a, SectionTitle<'a>, E> {
     let parse_level = map(fold_many_m_n(1, 6, tag("="), 0, |acc, _| acc + 1), |level| level - 1);
-    let mut parse_level = terminated(parse_level, ws);
+    let mut parse_level = terminated(parse_level, ws1);
     let (i, level) = parse_level(i)?;
 
-    let (i, content) = take_line(i)?;
+    let (i, content) = terminated(take_till(|c| c == '\n'), newline_or_eof)(i)?;
+
+    Ok((i, SectionTitle { level, content, style: None }))
+}
+
+/// Splits a trailing anchor, like `[[id]]` or `[#id]`, off of a section title, returning the
+/// remaining title and the anchor's id.
+fn split_trailing_anchor(content: Span<'_>) -> Option<(Span<'_>, Span<'_>)> {
+    let text = content.trim_end();
+    let (start, anchor) = if text.ends_with("]]") {
+        let start = text.rfind("[[")?;
+        (start, text.slice(start + 2..text.len() - 2))
+    } else if text.ends_with(']') {
+        let start = text.rfind("[#")?;
+        (start, text.slice(start + 2..text.len() - 1))
+    } else {
+        return None;
+    };
+    // ids start with a letter, `_` or `:`
+    let is_id_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '.');
+    let mut chars = anchor.chars();
+    let valid = matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_' || c == ':')
+        && chars.all(is_id_char);
+    if !valid {
+        return None;
+    }
+    let title = text.slice(..start).trim_end();
+    if title.is_empty() || title.len() == start {
+        // the anchor has to be separated from the title by whitespace
+        return None;
+    }
+    Some((title, anchor))
+}
+
+/// Parses a section, consisting of its title and all following blocks up until the next section
+/// title of the same or a lower level. The section's style is read from `attributes`, a discrete
+/// heading doesn't contain any blocks.
+pub fn parse_section<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+    attributes: &AttributeList<'a>,
+) -> PResult<'a, (SectionTitle<'a>, Blocks<'a>), E> {
+    let (i, mut title) = parse_section_title(i)?;
+    title.style = SectionStyle::from_attributes(attributes);
+    if title.style == Some(SectionStyle::Discrete) {
+        return Ok((i, (title, Vec::new())));
+    }
+
+    let level = title.level;
+    // the metadata lines, like an anchor, preceding the next section title belong to it
+    let section_end = verify(
+        preceded(many0(ws_with_nl), pair(parse_block_metadata, parse_section_title)),
+        |(metadata, next): &(BlockMetadata, SectionTitle)| {
+            let style = SectionStyle::from_attributes(&metadata.attributes);
+            next.level <= level && style != Some(SectionStyle::Discrete)
+        },
+    );
+    let (i, mut blocks) = many0(preceded(not(section_end), parse_attributed_block))(i)?;
+    if title.style == Some(SectionStyle::Bibliography) {
+        parse_bibliography_entries(&mut blocks);
+    }
+    Ok((i, (title, blocks)))
+}
+
+/// Turns the paragraphs and list items of a bibliography section that start with a
+/// `[[[id]]]` anchor into bibliography entries.
+fn parse_bibliography_entries(blocks: &mut Blocks<'_>) {
+    for block in blocks {
+        match &mut block.context {
+            Context::Paragraph(paragraph) => {
+                if let Ok((rest, (id, reftext))) = parse_bibliography_anchor::<()>(*paragraph) {
+                    let entry = BibliographyEntry { id, reftext };
+                    block.context =
+                        Context::BibliographyEntry { entry, content: rest.trim_start() };
+                }
+            }
+            Context::List(list) => {
+                for item in &mut list.items {
+                    let first = match item.paragraph.first() {
+                        Some(&Tag::Text(first)) => first,
+                        _ => continue,
+                    };
+                    if let Ok((rest, (id, reftext))) = parse_bibliography_anchor::<()>(first) {
+                        let rest = rest.trim_start();
+                        if rest.is_empty() {
+                            item.paragraph.remove(0);
+                        } else {
+                            item.paragraph[0] = Tag::Text(rest);
+                        }
+                        item.bibliography = Some(BibliographyEntry { id, reftext });
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Parses a bibliography anchor, like `[[[knuth]]]` or `[[[knuth,Knuth 1968]]]`, into its id
+/// and reftext.
+pub fn parse_bibliography_anchor<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> PResult<'a, (Span<'a>, Option<Span<'a>>), E> {
+    delimited(tag("["), parse_block_anchor, tag("]"))(i)
+}
+
+/// Parses a literal paragraph, which is a paragraph whose first line is indented.
+pub fn parse_literal_paragraph<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> PResult<'a, Span<'a>, E> {
+    recognize(preceded(peek(ws1), many1(take_nonblank_line)))(i)
+}
+
+/// Replaces the tabs in `text` with spaces up to the next multiple of `tab_size` columns, as is
+/// done for the content of verbatim blocks. A `tab_size` of 0 leaves tabs as they are.
+pub fn expand_tabs(text: &str, tab_size: usize) -> Cow<'_, str> {
+    if tab_size == 0 || !text.contains('\t') {
+        return Cow::Borrowed(text);
+    }
+    let mut expanded = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let width = tab_size - column % tab_size;
+                expanded.extend(std::iter::repeat_n(' ', width));
+                column += width;
+            }
+            '\n' => {
+                expanded.push(c);
+                column = 0;
+            }
+            _ => {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+    }
+    Cow::Owned(expanded)
+}
+
+/// Splits the content of a literal block into its lines, with the indentation common to all
+/// non-blank lines removed.
+///
+/// Tabs advance the indentation to the next multiple of `tab_size` columns.
+pub fn dedent_lines(content: Span<'_>, tab_size: usize) -> Vec<Span<'_>> {
+    let tab_size = tab_size.max(1);
+    let advance = |column: usize, c: char| match c {
+        '\t' => (column / tab_size + 1) * tab_size,
+        _ => column + 1,
+    };
+    let lines: Vec<_> = content.lines().collect();
 
-    Ok((i, SectionTitle { level, content }))
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.chars().take_while(|&c| c == ' ' || c == '\t').fold(0, advance))
+        .min()
+        .unwrap_or(0);
+    lines
+        .into_iter()
+        .map(|line| {
+            let mut column = 0;
+            let stripped = line
+                .char_indices()
+                .find(|&(_, c)| {
+                    let is_indent = column < indent && (c == ' ' || c == '\t');
+                    column = advance(column, c);
+                    !is_indent
+                })
+                .map_or(line.len(), |(idx, _)| idx);
+            line.slice(stripped..)
+        })
+        .collect()
 }
 
-pub fn parse_paragraph<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Vec<Span<'a>>, E> {
-    let (i1, (tags, _)) = many_till(take_line, tag("\n"))(i)?;
-    Ok((i1, tags))
+pub fn parse_paragraph<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
+    // lines that look like the start of a block macro or fenced block are never paragraphs
+    let block_start = alt((recognize(pair(alpha1, tag("::"))), tag("```")));
+    let lines = recognize(preceded(not(block_start), many1(take_nonblank_line)));
+    map(lines, |paragraph: Span<'a>| paragraph.trim_end())(i)
 }
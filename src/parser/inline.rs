@@ -0,0 +1,347 @@
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_until, take_while1};
+use nom::character::complete::none_of;
+use nom::combinator::{map, map_opt, opt, recognize};
+use nom::error::{ErrorKind, ParseError};
+use nom::multi::{many0, separated_list1};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+use nom::{InputIter, Offset, Slice};
+
+use crate::ast::*;
+use crate::parser::nom_ext::bounded;
+use crate::parser::PResult;
+use crate::Span;
+
+pub fn parse_inlines<'a, E: ParseError<Span<'a>>>(mut i: Span<'a>) -> PResult<'a, Tags<'a>, E> {
+    let mut tags = Vec::new();
+    let mut text = i;
+    let mut prev = None;
+    while let Some(c) = i.iter_elements().next() {
+        // constrained formatting marks may not start in the middle of a word
+        let res = match prev {
+            Some(prev) if is_word_char(prev) => {
+                alt((parse_passthrough, parse_index_term, parse_footnote))(i)
+            }
+            _ => parse_inline(i),
+        };
+        prev = Some(c);
+        match res {
+            Ok((rest, tag)) => {
+                if text.offset(&i) > 0 {
+                    tags.push(Tag::Text(text.slice(..text.offset(&i))));
+                }
+                tags.push(tag);
+                i = rest;
+                text = i;
+            }
+            Err(nom::Err::Error(_)) => i = i.slice(c.len_utf8()..),
+            Err(e) => return Err(e),
+        }
+    }
+    if !text.is_empty() {
+        tags.push(Tag::Text(text));
+    }
+    Ok((i, tags))
+}
+
+/// Parses the inlines of the first line of `i`, returning the input following the line's newline
+/// as the remainder. This allows parsing text line by line, e.g. while it is still being read,
+/// as long as no inline spans multiple lines. Fails on empty input.
+pub fn parse_inlines_line<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Tags<'a>, E> {
+    if i.is_empty() {
+        return Err(nom::Err::Error(E::from_error_kind(i, ErrorKind::Eof)));
+    }
+    let (line, rest) = match i.find('\n') {
+        Some(end) => (i.slice(..end), i.slice(end + 1..)),
+        None => (i, i.slice(i.len()..)),
+    };
+    let (_, tags) = parse_inlines(line)?;
+    Ok((rest, tags))
+}
+
+/// Parses the inlines of a paragraph, excluding its trailing newline.
+///
+/// If `hardbreaks` is set, every newline within the paragraph is turned into a
+/// [`Tag::LineBreak`]. [`parse_block_text`] sets it for paragraphs with the `hardbreaks` option,
+/// which all paragraphs of a document setting the `hardbreaks-option` attribute have.
+pub fn parse_paragraph_inlines<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+    hardbreaks: bool,
+) -> PResult<'a, Tags<'a>, E> {
+    let content = i.slice(..i.trim_end_matches('\n').len());
+    let (_, tags) = parse_inlines(content)?;
+    let rest = i.slice(content.len()..);
+    if !hardbreaks {
+        return Ok((rest, tags));
+    }
+    let mut broken = Vec::with_capacity(tags.len());
+    for tag in tags {
+        match tag {
+            Tag::Text(mut text) => {
+                while let Some(idx) = text.find('\n') {
+                    if idx > 0 {
+                        broken.push(Tag::Text(text.slice(..idx)));
+                    }
+                    broken.push(Tag::LineBreak);
+                    text = text.slice(idx + 1..);
+                }
+                if !text.is_empty() {
+                    broken.push(Tag::Text(text));
+                }
+            }
+            tag => broken.push(tag),
+        }
+    }
+    Ok((rest, broken))
+}
+
+pub fn parse_inline<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Tag<'a>, E> {
+    alt((
+        parse_passthrough,
+        parse_index_term,
+        parse_footnote,
+        parse_stem,
+        parse_ui_macro,
+        parse_format,
+    ))(i)
+}
+
+/// Parses a footnote, either a `footnote:[text]` or `footnote:id[text]` definition or a
+/// `footnote:id[]` reference, or their legacy forms `footnoteref:[id,text]` and
+/// `footnoteref:[id]`.
+pub fn parse_footnote<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Tag<'a>, E> {
+    let footnote = pair(
+        preceded(tag("footnote:"), opt(take_while1(|c: char| is_word_char(c) || c == '-'))),
+        escaped_brackets,
+    );
+    let footnoteref = map(
+        preceded(tag("footnoteref:"), escaped_brackets),
+        |attributes: Span<'a>| match attributes.find(',') {
+            Some(idx) => (Some(attributes.slice(..idx).trim()), attributes.slice(idx + 1..)),
+            None => (Some(attributes.trim()), attributes.slice(attributes.len()..)),
+        },
+    );
+    let (rest, (id, text)) = alt((footnote, footnoteref))(i)?;
+    let id = id.filter(|id| !id.is_empty());
+    let text = text.trim();
+    if id.is_none() && text.is_empty() {
+        return Err(nom::Err::Error(E::from_error_kind(i, ErrorKind::Verify)));
+    }
+    let content = if text.is_empty() { None } else { Some(parse_inlines(text)?.1) };
+    Ok((rest, Tag::Footnote { id, content }))
+}
+
+/// Parses the bracketed text of a macro, in which a `]` may be escaped as `\]`. Like in attribute
+/// values, the backslash is kept in the text.
+fn escaped_brackets<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
+    let text = recognize(many0(alt((tag("\\]"), recognize(none_of("]"))))));
+    delimited(tag("["), text, tag("]"))(i)
+}
+
+/// Parses a UI macro, either a button like `btn:[OK]` or a menu selection like
+/// `menu:View[Zoom > Reset]`.
+pub fn parse_ui_macro<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Tag<'a>, E> {
+    let button = map_opt(
+        preceded(tag("btn:"), delimited(tag("["), take_until("]"), tag("]"))),
+        |label: Span<'a>| Some(label.trim()).filter(|label| !label.is_empty()).map(Tag::Button),
+    );
+    let menu = map(
+        pair(
+            preceded(tag("menu:"), take_while1(|c: char| c != '[' && !c.is_whitespace())),
+            delimited(tag("["), take_until("]"), tag("]")),
+        ),
+        |(menu, items)| Tag::Menu(Some(menu).into_iter().chain(split_menu_items(items)).collect()),
+    );
+    alt((button, menu))(i)
+}
+
+/// Splits the submenus and item of a menu macro, like `Zoom > Reset`, at each `>` that isn't
+/// part of a quoted segment like `"Fit > Width"`. Segments are trimmed and unquoted.
+fn split_menu_items(items: Span<'_>) -> Vec<Span<'_>> {
+    if items.trim().is_empty() {
+        return Vec::new();
+    }
+    let mut split = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let ends = items.char_indices().filter_map(|(idx, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        '>' if !quoted => Some(idx),
+        _ => None,
+    });
+    for end in ends.chain(Some(items.len())) {
+        let segment = items.slice(start..end).trim();
+        let unquoted = segment.len() >= 2 && segment.starts_with('"') && segment.ends_with('"');
+        split.push(if unquoted { segment.slice(1..segment.len() - 1) } else { segment });
+        start = end + 1;
+    }
+    split
+}
+
+/// Parses an index term, either in the visible `((term))` or invisible `(((primary,secondary)))`
+/// form, or as an `indexterm2:[term]` or `indexterm:[primary,secondary]` macro.
+pub fn parse_index_term<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Tag<'a>, E> {
+    let delimited_terms = |open, close| delimited(tag(open), take_until(close), tag(close));
+    let (rest, (terms, visible)) = alt((
+        map(delimited_terms("(((", ")))"), |terms| (terms, false)),
+        map(delimited_terms("((", "))"), |terms| (terms, true)),
+        map(preceded(tag("indexterm2:"), delimited_terms("[", "]")), |terms| (terms, true)),
+        map(preceded(tag("indexterm:"), delimited_terms("[", "]")), |terms| (terms, false)),
+    ))(i)?;
+    let terms = if visible { vec![terms.trim()] } else { split_terms(terms) };
+    match *terms.as_slice() {
+        [primary, ..] if !primary.is_empty() => Ok((
+            rest,
+            Tag::IndexTerm {
+                primary,
+                secondary: terms.get(1).copied(),
+                tertiary: terms.get(2).copied(),
+                visible,
+            },
+        )),
+        _ => Err(nom::Err::Error(E::from_error_kind(i, ErrorKind::Verify))),
+    }
+}
+
+/// Splits a comma separated list of index terms, trimming each.
+fn split_terms(terms: Span<'_>) -> Vec<Span<'_>> {
+    let mut split = Vec::new();
+    let mut start = 0;
+    for (idx, _) in terms.match_indices(',').chain(Some((terms.len(), ""))) {
+        split.push(terms.slice(start..idx).trim());
+        start = idx + 1;
+    }
+    split
+}
+
+/// Parses inline math, like `stem:[x^2]`, `latexmath:[x^2]` or `asciimath:[x^2]`.
+pub fn parse_stem<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Tag<'a>, E> {
+    let name = alt((tag("stem"), tag("latexmath"), tag("asciimath")));
+    let notation =
+        map_opt(terminated(name, tag(":")), |name: Span<'a>| StemNotation::from_style(name.text()));
+    map(pair(notation, delimited(tag("["), take_until("]"), tag("]"))), |(notation, content)| {
+        Tag::Stem { notation, content }
+    })(i)
+}
+
+/// Parses constrained formatting, like `*bold*`, `_italic_` or `` `monospace` ``.
+///
+/// The content of bold and italic text is parsed for nested inlines, while monospace content is
+/// kept literal.
+pub fn parse_format<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Tag<'a>, E> {
+    let (kind, marker) = match i.iter_elements().next() {
+        Some('*') => (FormatKind::Bold, '*'),
+        Some('_') => (FormatKind::Italic, '_'),
+        Some('`') => (FormatKind::Monospace, '`'),
+        _ => return Err(nom::Err::Error(E::from_error_kind(i, ErrorKind::Char))),
+    };
+    let content = i.slice(1..);
+    let end = match find_closing_marker(&content, marker) {
+        Some(end) => end,
+        None => return Err(nom::Err::Error(E::from_error_kind(i, ErrorKind::TakeUntil))),
+    };
+    let rest = content.slice(end + 1..);
+    let content = content.slice(..end);
+    let tags = match kind {
+        FormatKind::Monospace => vec![Tag::Text(content)],
+        _ => parse_inlines(content)?.1,
+    };
+    Ok((rest, Tag::Format(kind, tags)))
+}
+
+/// Finds the closing `marker` of a constrained formatting pair, which has to follow a
+/// non-whitespace character and may not be followed by a word character.
+pub(crate) fn find_closing_marker(content: &str, marker: char) -> Option<usize> {
+    if content.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut chars = content.char_indices().peekable();
+    let mut prev = None;
+    while let Some((idx, c)) = chars.next() {
+        let closes = c == marker
+            && matches!(prev, Some(prev) if !char::is_whitespace(prev))
+            && !matches!(chars.peek(), Some(&(_, next)) if is_word_char(next));
+        if closes {
+            return Some(idx);
+        }
+        prev = Some(c);
+    }
+    None
+}
+
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+pub fn parse_passthrough<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Tag<'a>, E> {
+    let triple_plus = map(delimited(tag("+++"), take_until("+++"), tag("+++")), |content| {
+        Tag::Passthrough { subs: Subs::new(), content }
+    });
+    let pass_macro = map(
+        tuple((
+            preceded(tag("pass:"), opt(parse_subs)),
+            delimited(tag("["), take_until("]"), tag("]")),
+        )),
+        |(subs, content)| Tag::Passthrough { subs: subs.unwrap_or_default(), content },
+    );
+    alt((triple_plus, pass_macro))(i)
+}
+
+/// The substitutions applied to the text of a block with the given context by default.
+pub fn default_subs(context: &Context<'_>) -> Subs {
+    match context {
+        Context::Paragraph(_) => Substitution::NORMAL.to_vec(),
+        Context::Listing(_) | Context::Literal(_) => Substitution::VERBATIM.to_vec(),
+        _ => Subs::new(),
+    }
+}
+
+/// Parses the text of a block into inlines according to the block's substitutions, see
+/// [`Block::subs`], returning `None` for blocks that don't contain text. Only the quotes
+/// substitution produces inline markup, without it the text is kept as is. Paragraphs with the
+/// `hardbreaks` option keep their line breaks.
+pub fn parse_block_text<'a>(block: &Block<'a>) -> Option<Tags<'a>> {
+    let text = match block.context {
+        Context::Paragraph(text)
+        | Context::Listing(text)
+        | Context::Literal(text)
+        | Context::Passthrough(text) => text,
+        _ => return None,
+    };
+    let text = text.slice(..text.trim_end_matches('\n').len());
+    if !block.subs().contains(&Substitution::Quotes) {
+        return Some(vec![Tag::Text(text)]);
+    }
+    let tags = match block.context {
+        Context::Paragraph(_) => {
+            bounded(|i| parse_paragraph_inlines::<()>(i, block.has_option("hardbreaks")))(text)
+        }
+        _ => bounded(parse_inlines::<()>)(text),
+    };
+    tags.ok().map(|(_, tags)| tags)
+}
+
+/// Parses a comma separated list of substitutions and substitution groups, like `a,q` or
+/// `verbatim`.
+pub fn parse_subs<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Subs, E> {
+    let substitution =
+        map_opt(take_while1(|c: char| c.is_ascii_alphabetic() || c == '_'), |name: Span<'a>| {
+            use Substitution::*;
+            Some(match name.text() {
+                "n" | "normal" => Substitution::NORMAL,
+                "v" | "verbatim" => Substitution::VERBATIM,
+                "none" => &[],
+                "c" | "specialchars" | "specialcharacters" => &[SpecialCharacters],
+                "q" | "quotes" => &[Quotes],
+                "a" | "attributes" => &[Attributes],
+                "r" | "replacements" => &[Replacements],
+                "m" | "macros" => &[Macros],
+                "p" | "post_replacements" => &[PostReplacements],
+                _ => return None,
+            })
+        });
+    map(separated_list1(tag(","), substitution), |subs: Vec<&[Substitution]>| subs.concat())(i)
+}
@@ -0,0 +1,172 @@
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_till, take_while_m_n};
+use nom::character::complete::{digit1, newline};
+use nom::combinator::{map, not, opt, peek, recognize, verify};
+use nom::error::ParseError;
+use nom::multi::{many0, many1};
+use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::Slice;
+
+use crate::ast::*;
+use crate::parser::nom_ext::*;
+use crate::parser::{parse_attributed_block, parse_inlines, PResult};
+use crate::Span;
+
+/// The marker of a list item, items with equal markers belong to the same list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Marker<'a> {
+    Unordered(&'a str),
+    Ordered(&'a str),
+    Description(&'a str),
+}
+
+pub fn parse_list<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, List<'a>, E> {
+    parse_list_with_ancestors(i, &mut Vec::new())
+}
+
+/// Parses a list whose items all share the marker of the first item. Items with a different
+/// marker that isn't used by one of the enclosing lists start a nested list.
+fn parse_list_with_ancestors<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+    ancestors: &mut Vec<Marker<'a>>,
+) -> PResult<'a, List<'a>, E> {
+    let (_, marker) = peek(parse_list_marker)(i)?;
+    ancestors.push(marker);
+    let level = ancestors.len();
+
+    let mut items = Vec::new();
+    let mut i = i;
+    loop {
+        let (next, _) = many0(ws_with_nl)(i)?;
+        match peek(parse_list_marker::<E>)(next) {
+            Ok((_, m)) if m == marker => {
+                let (rest, item) = parse_list_item(next, level)?;
+                items.push(item);
+                i = rest;
+            }
+            Ok((_, m)) if !items.is_empty() && !ancestors.contains(&m) => {
+                let (rest, list) = parse_list_with_ancestors(next, ancestors)?;
+                if let Some(item) = items.last_mut() {
+                    item.blocks.push(Block::new(Context::List(list)));
+                }
+                i = rest;
+            }
+            _ => break,
+        }
+    }
+
+    ancestors.pop();
+    Ok((i, List { items, style: None, start: None }))
+}
+
+fn parse_list_item<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+    level: usize,
+) -> PResult<'a, ListItem<'a>, E> {
+    let (_, marker) = peek(parse_list_marker)(i)?;
+    let (i, mut item) = match marker {
+        Marker::Unordered(_) | Marker::Ordered(_) => {
+            let (i, _) = parse_list_marker(i)?;
+            let checkbox =
+                alt((map(tag("[ ]"), |_| false), map(alt((tag("[x]"), tag("[*]"))), |_| true)));
+            let (i, checked) = match marker {
+                Marker::Unordered(_) => opt(terminated(checkbox, ws1))(i)?,
+                _ => (i, None),
+            };
+            let (i, text) = parse_list_item_text(i)?;
+            let (_, paragraph) = bounded(parse_inlines)(text)?;
+            let kind = match (marker, checked) {
+                (_, Some(checked)) => ListItemKind::Checklist(checked),
+                (Marker::Ordered(_), _) => ListItemKind::Ordered,
+                _ => ListItemKind::Unordered,
+            };
+            (i, ListItem { kind, level, paragraph, blocks: Vec::new(), bibliography: None })
+        }
+        Marker::Description(_) => {
+            let (i, (term, _)) = parse_description_term(i)?;
+            let (_, term) = bounded(parse_inlines)(term)?;
+            // the definition may start on the line following the term, unless that line starts
+            // a nested list making up the definition instead
+            let (i, _) = opt(preceded(ws, newline))(i)?;
+            let (i, definition) =
+                opt(preceded(pair(ws, not(parse_list_marker)), parse_list_item_text))(i)?;
+            let blocks = definition.into_iter().map(paragraph_block).collect();
+            let kind = ListItemKind::Description(term);
+            (i, ListItem { kind, level, paragraph: Tags::new(), blocks, bibliography: None })
+        }
+    };
+    let (i, attached) = many0(parse_attached_block)(i)?;
+    item.blocks.extend(attached);
+    Ok((i, item))
+}
+
+/// Parses a block attached to the preceding list item, either via a list continuation (a line
+/// consisting of a single `+`) or by being an indented paragraph following a blank line.
+fn parse_attached_block<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Block<'a>, E> {
+    let not_a_paragraph =
+        verify(parse_attributed_block, |block| !matches!(block.context, Context::Paragraph(_)));
+    let continuation = preceded(
+        list_continuation,
+        alt((not_a_paragraph, map(parse_list_item_text, paragraph_block))),
+    );
+    let indented = preceded(
+        pair(many1(ws_with_nl), not(parse_list_marker)),
+        map(preceded(ws1, parse_list_item_text), paragraph_block),
+    );
+    alt((continuation, indented))(i)
+}
+
+fn paragraph_block(text: Span<'_>) -> Block<'_> {
+    Block::new(Context::Paragraph(text))
+}
+
+/// Parses the text of a list item, which extends over all following lines up until a blank
+/// line, a list continuation or the start of another list item.
+fn parse_list_item_text<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
+    let line = || verify(take_till(|c| c == '\n'), |line: &Span<'a>| !line.trim().is_empty());
+    let continuation_line =
+        preceded(newline, preceded(pair(not(parse_list_marker), not(list_continuation)), line()));
+    terminated(recognize(pair(line(), many0(continuation_line))), newline_or_eof)(i)
+}
+
+fn list_continuation<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
+    terminated(tag("+"), ws_with_nl)(i)
+}
+
+/// Recognizes the marker of a list item without consuming the description term.
+fn parse_list_marker<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Marker<'a>, E> {
+    let unordered = alt((take_while_m_n(1, 5, |c| c == '*'), tag("-")));
+    let ordered = alt((
+        take_while_m_n(1, 5, |c| c == '.'),
+        map(recognize(pair(digit1, tag("."))), |_| Span::new(".")),
+    ));
+    alt((
+        map(delimited(ws, unordered, ws1), |marker: Span<'a>| Marker::Unordered(marker.text())),
+        map(delimited(ws, ordered, ws1), |marker: Span<'a>| Marker::Ordered(marker.text())),
+        map(parse_description_term, |(_, marker)| Marker::Description(marker.text())),
+    ))(i)
+}
+
+/// Parses a description list term, returning the term and its marker.
+fn parse_description_term<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> PResult<'a, (Span<'a>, Span<'a>), E> {
+    let (_, line) = take_till(|c| c == '\n')(i)?;
+    let text = line.text();
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find(&[':', ';'][..]).map(|p| p + pos) {
+        let c = text.as_bytes()[start];
+        let len = text[start..].bytes().take_while(|&b| b == c).count();
+        let end = start + len;
+        let valid_len = if c == b':' { (2..=4).contains(&len) } else { len == 2 };
+        let terminated = matches!(text[end..].chars().next(), None | Some(' ') | Some('\t'));
+        if start > 0 && valid_len && terminated && !text[..start].trim().is_empty() {
+            let term = line.slice(..start);
+            let marker = line.slice(start..end);
+            let (i, _) = ws(i.slice(end..))?;
+            return Ok((i, (term.trim_start(), marker)));
+        }
+        pos = end;
+    }
+    Err(nom::Err::Error(E::from_error_kind(i, nom::error::ErrorKind::Tag)))
+}
@@ -1,19 +1,219 @@
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_until, take_while, take_while1};
+use nom::bytes::complete::{tag, take_till, take_while, take_while1};
 use nom::character::complete::newline;
-use nom::combinator::{eof, map, peek, recognize, rest_len, verify};
+use nom::combinator::{eof, recognize, verify};
 use nom::error::ParseError;
 use nom::sequence::{delimited, terminated};
-use nom::Parser;
+use nom::{Parser, Slice};
 
+use crate::ast::*;
 use crate::parser::PResult;
 use crate::span::Span;
 
-pub fn take_until1<'a, E>(tag: &'static str) -> impl FnMut(Span<'a>) -> PResult<'a, Span<'a>, E>
+/// Wraps `parser`, asserting in debug builds that the input it leaves over lies within the input
+/// it was given and that all spans of its output lie within the part of the input it consumed.
+/// This catches parsers that fabricate spans escaping their input, e.g. when fuzzing.
+pub fn bounded<'a, E, O, P>(mut parser: P) -> impl FnMut(Span<'a>) -> PResult<'a, O, E>
 where
     E: ParseError<Span<'a>>,
+    O: Spanned<'a>,
+    P: Parser<Span<'a>, O, E>,
 {
-    verify(take_until(tag), |s: &Span<'a>| s.len() > 1)
+    move |i: Span<'a>| {
+        let (rest, output) = parser.parse(i)?;
+        debug_assert!(
+            rest.location_offset() >= i.location_offset(),
+            "remaining input at {} precedes the parsed input at {}",
+            rest.location_offset(),
+            i.location_offset(),
+        );
+        debug_assert!(
+            rest.within(&i),
+            "remaining input {}..{} escapes the parsed input {}..{}",
+            rest.location_offset(),
+            rest.end_offset(),
+            i.location_offset(),
+            i.end_offset(),
+        );
+        if cfg!(debug_assertions) {
+            let consumed = i.slice(..rest.location_offset() - i.location_offset());
+            output.for_each_span(&mut |span| {
+                assert!(
+                    span.within(&consumed),
+                    "output span {}..{} escapes the consumed input {}..{}",
+                    span.location_offset(),
+                    span.end_offset(),
+                    consumed.location_offset(),
+                    consumed.end_offset(),
+                )
+            });
+        }
+        Ok((rest, output))
+    }
+}
+
+/// The output of a parser, whose spans [`bounded`] checks to lie within the parsed input.
+pub trait Spanned<'a> {
+    /// Calls `f` with every span contained in `self`, including those of nested nodes.
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>));
+}
+
+impl<'a> Spanned<'a> for Span<'a> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        f(*self)
+    }
+}
+
+impl<'a, T: Spanned<'a>> Spanned<'a> for Option<T> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        if let Some(it) = self {
+            it.for_each_span(f)
+        }
+    }
+}
+
+impl<'a, T: Spanned<'a>> Spanned<'a> for Vec<T> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        self.iter().for_each(|it| it.for_each_span(f))
+    }
+}
+
+impl<'a> Spanned<'a> for Block<'a> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        let Block { context, title, attributes: _, id, roles, options, callouts } = self;
+        context.for_each_span(f);
+        title.for_each_span(f);
+        id.for_each_span(f);
+        roles.for_each_span(f);
+        options.for_each_span(f);
+        callouts.iter().for_each(|callout| f(callout.text));
+    }
+}
+
+impl<'a> Spanned<'a> for Context<'a> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        match self {
+            Context::SectionTitle(title, blocks) => {
+                f(title.content);
+                blocks.for_each_span(f);
+            }
+            Context::Admonition { label, blocks } => {
+                f(*label);
+                blocks.for_each_span(f);
+            }
+            Context::Example(blocks)
+            | Context::Sidebar(blocks)
+            | Context::Open(blocks)
+            | Context::Quote(blocks) => blocks.for_each_span(f),
+            Context::Listing(span)
+            | Context::Literal(span)
+            | Context::Paragraph(span)
+            | Context::Passthrough(span)
+            | Context::Stem { content: span, .. }
+            | Context::Error(span)
+            | Context::Comment(span) => f(*span),
+            Context::BibliographyEntry { entry, content } => {
+                entry.for_each_span(f);
+                f(*content);
+            }
+            Context::Verse(verse) => {
+                f(verse.content);
+                verse.attribution.for_each_span(f);
+                verse.citation.for_each_span(f);
+            }
+            Context::List(list) => list.for_each_span(f),
+            Context::Table(table) => table.for_each_span(f),
+            Context::BlockMacro(macro_) => macro_.for_each_span(f),
+            Context::ThematicBreak | Context::PageBreak => (),
+        }
+    }
+}
+
+impl<'a> Spanned<'a> for BibliographyEntry<'a> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        f(self.id);
+        self.reftext.for_each_span(f);
+    }
+}
+
+impl<'a> Spanned<'a> for Macro<'a> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        f(self.name);
+        f(self.target);
+    }
+}
+
+impl<'a> Spanned<'a> for List<'a> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        self.items.for_each_span(f)
+    }
+}
+
+impl<'a> Spanned<'a> for ListItem<'a> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        if let ListItemKind::Description(term) = &self.kind {
+            term.for_each_span(f);
+        }
+        self.paragraph.for_each_span(f);
+        self.blocks.for_each_span(f);
+        self.bibliography.for_each_span(f);
+    }
+}
+
+impl<'a> Spanned<'a> for Table<'a> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        self.header.for_each_span(f);
+        self.rows.for_each_span(f);
+        self.footer.for_each_span(f);
+    }
+}
+
+impl<'a> Spanned<'a> for TableCell<'a> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        f(self.content)
+    }
+}
+
+impl<'a> Spanned<'a> for Tag<'a> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        match self {
+            Tag::Text(span)
+            | Tag::Anchor(span)
+            | Tag::Button(span)
+            | Tag::Stem { content: span, .. }
+            | Tag::Passthrough { content: span, .. } => f(*span),
+            Tag::LineBreak => (),
+            Tag::Format(_, tags) => tags.for_each_span(f),
+            Tag::Mark(mark) => {
+                mark.content.for_each_span(f);
+                mark.attributes.for_each_span(f);
+            }
+            Tag::Link(link) => {
+                f(link.href);
+                link.content.for_each_span(f);
+                link.attributes.for_each_span(f);
+            }
+            Tag::InlineMacro(macro_) => macro_.for_each_span(f),
+            Tag::IndexTerm { primary, secondary, tertiary, .. } => {
+                f(*primary);
+                secondary.for_each_span(f);
+                tertiary.for_each_span(f);
+            }
+            Tag::Footnote { id, content } => {
+                id.for_each_span(f);
+                content.for_each_span(f);
+            }
+            Tag::Menu(items) => items.for_each_span(f),
+        }
+    }
+}
+
+impl<'a> Spanned<'a> for Attribute<'a> {
+    fn for_each_span(&self, f: &mut dyn FnMut(Span<'a>)) {
+        match self {
+            Attribute::Id(span) | Attribute::Role(span) | Attribute::Option(span) => f(*span),
+        }
+    }
 }
 
 pub fn ws<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
@@ -38,31 +238,13 @@ pub fn newline_or_eof<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, S
     alt((recognize(newline), eof))(i)
 }
 
-pub fn spacer<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
-    take_while1(is_spacer)(i)
-}
-
-pub fn rspaced<'a, F, O, E>(f: F) -> impl FnMut(Span<'a>) -> PResult<'a, O, E>
-where
-    E: ParseError<Span<'a>>,
-    F: Fn(Span<'a>) -> PResult<'a, O, E>,
-{
-    let eof = recognize(verify(rest_len, |&i| i == 0));
-    let is_end = map(peek(eof), |_| ());
-    let after = alt((is_end, map(peek(spacer), |_| ())));
-    terminated(f, after)
-}
-
-pub fn is_spacer(char: char) -> bool {
-    match char {
-        '\\' => false,
-        _ => !char.is_alphanumeric(),
-    }
+/// Takes a line that contains more than just whitespace, the line may be terminated by eof.
+pub fn take_nonblank_line<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
+    verify(terminated(take_till(|c| c == '\n'), newline_or_eof), |line: &Span<'a>| {
+        !line.trim().is_empty()
+    })(i)
 }
 
-pub fn take_line<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
-    terminated(take_until("\n"), tag("\n"))(i)
-}
 pub fn ws_with_nl<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> PResult<'a, Span<'a>, E> {
     terminated(ws, tag("\n"))(i)
 }
@@ -0,0 +1,219 @@
+use nom::bytes::complete::{tag, take_till};
+use nom::character::complete::newline;
+use nom::error::{ErrorKind, ParseError};
+use nom::sequence::terminated;
+use nom::{Offset, Slice};
+
+use crate::ast::*;
+use crate::parser::inline::{find_closing_marker, is_word_char, parse_passthrough};
+use crate::parser::nom_ext::*;
+use crate::parser::PResult;
+use crate::Span;
+
+/// Parses a table delimited by `|===`, laying out its cells according to the `cols` attribute.
+/// The table's layout is read from the `width`, `frame` and `grid` attributes and the `header`,
+/// `footer` and `autowidth` options, given either as `%option` or in the `options` attribute.
+pub fn parse_table<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+    attributes: &AttributeList<'a>,
+    options: &[Span<'a>],
+) -> PResult<'a, Table<'a>, E> {
+    let (content, _) = terminated(tag("|==="), newline)(i)?;
+
+    let mut rest = content;
+    let (rest, content) = loop {
+        if rest.is_empty() {
+            return Err(nom::Err::Error(E::from_error_kind(rest, ErrorKind::TakeUntil)));
+        }
+        let (next, line) = take_till(|c| c == '\n')(rest)?;
+        if line.trim_end().text() == "|===" {
+            break (next, content.slice(..content.offset(&rest)));
+        }
+        let (next, _) = newline_or_eof(next)?;
+        rest = next;
+    };
+
+    let attribute = |name| attributes.get(name).copied().flatten();
+    let has_option = |name| {
+        options.iter().any(|option| option.text() == name)
+            || attribute("options").is_some_and(|opts| opts.split(',').any(|it| it.trim() == name))
+    };
+
+    let first_line = content.slice(..content.find('\n').unwrap_or(content.len()));
+    let rest_lines = content.slice(first_line.len()..);
+    // a first line that is followed by a blank line makes up an implicit header row
+    let implicit_header = rest_lines.strip_prefix('\n').and_then(|it| it.lines().next());
+    let (header, cells) = match implicit_header {
+        Some(line) if line.trim().is_empty() && first_line.starts_with('|') => {
+            (Some(parse_cells(first_line)), parse_cells(rest_lines))
+        }
+        _ => (None, parse_cells(content)),
+    };
+
+    let columns = attributes.get("cols").copied().flatten().and_then(parse_cols);
+    let column_count =
+        columns.as_ref().map_or_else(|| parse_cells(first_line).len(), Vec::len).max(1);
+    let columns = columns.unwrap_or_else(|| vec![ColumnSpec::default(); column_count]);
+    let mut rows: Vec<_> = cells.chunks(column_count).map(<[_]>::to_vec).collect();
+
+    // the header and footer options promote the first and last row respectively
+    let header = match header {
+        None if has_option("header") && !rows.is_empty() => Some(rows.remove(0)),
+        header => header,
+    };
+    let footer = if has_option("footer") { rows.pop() } else { None };
+
+    let autowidth = has_option("autowidth");
+    let width = attribute("width");
+    let frame = attribute("frame").and_then(Frame::from_value).unwrap_or_default();
+    let grid = attribute("grid").and_then(Grid::from_value).unwrap_or_default();
+
+    Ok((rest, Table { columns, header, rows, footer, autowidth, width, frame, grid }))
+}
+
+/// Splits the content of a table into cells, each of which starts with a `|`. The `|` may be
+/// preceded by a style letter, like in `a|`, which then applies to that cell only.
+fn parse_cells(content: Span<'_>) -> Vec<TableCell<'_>> {
+    // the start of each cell's specifier, the position of its `|` and its style
+    let separators: Vec<_> = cell_separators(content.text())
+        .into_iter()
+        .map(|idx| {
+            let before = &content[..idx];
+            let style =
+                before.chars().next_back().and_then(cell_style).filter(|_| {
+                    before[..idx - 1].chars().next_back().is_none_or(char::is_whitespace)
+                });
+            match style {
+                Some(style) => (idx - 1, idx, Some(style)),
+                None => (idx, idx, None),
+            }
+        })
+        .collect();
+    let ends = separators.iter().skip(1).map(|&(spec_start, ..)| spec_start);
+    separators
+        .iter()
+        .zip(ends.chain(Some(content.len())))
+        .map(|(&(_, start, style), end)| TableCell {
+            content: content.slice(start + 1..end).trim(),
+            style,
+        })
+        .collect()
+}
+
+/// Finds the `|`s separating cells. Escaped pipes, `\|`, and those within monospace or
+/// passthrough text on the same line, like `` `a | b` ``, `+a | b+` or `+++a | b+++`, don't
+/// separate cells. Constrained pairs follow the same rules as in inline text, so the `+`s in
+/// `1 + 2 | 3 + 4` don't enclose anything.
+fn cell_separators(content: &str) -> Vec<usize> {
+    let mut separators = Vec::new();
+    let mut idx = 0;
+    let mut prev = None;
+    while idx < content.len() {
+        let rest = &content[idx..];
+        let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        let c = rest.chars().next().unwrap_or_default();
+        let enclosed = match c {
+            '+' if line.starts_with("+++") => parse_passthrough::<()>(Span::new(line))
+                .ok()
+                .map(|(after, _)| line.len() - after.len()),
+            '`' | '+' if !prev.is_some_and(is_word_char) => {
+                find_closing_marker(&line[1..], c).map(|end| end + 2)
+            }
+            _ => None,
+        };
+        let len = if rest.starts_with("\\|") {
+            2
+        } else if c == '|' {
+            separators.push(idx);
+            1
+        } else {
+            enclosed.unwrap_or_else(|| c.len_utf8())
+        };
+        prev = content[..idx + len].chars().next_back();
+        idx += len;
+    }
+    separators
+}
+
+/// The style a cell specifier letter like the `a` in `a|` stands for.
+fn cell_style(c: char) -> Option<ColumnStyle> {
+    Some(match c {
+        'a' => ColumnStyle::AsciiDoc,
+        'd' => ColumnStyle::Default,
+        'e' => ColumnStyle::Emphasis,
+        'h' => ColumnStyle::Header,
+        'l' => ColumnStyle::Literal,
+        'm' => ColumnStyle::Monospace,
+        's' => ColumnStyle::Strong,
+        _ => return None,
+    })
+}
+
+/// Parses the value of a `cols` attribute, like `3`, `1,2` or `<.^2m,3*>`.
+pub fn parse_cols(cols: &str) -> Option<Vec<ColumnSpec>> {
+    let cols = cols.trim();
+    if let Ok(count) = cols.parse::<usize>() {
+        return Some(vec![ColumnSpec::default(); count]);
+    }
+    let mut columns = Vec::new();
+    for spec in cols.split(&[',', ';'][..]) {
+        let (multiplier, spec) = parse_column_spec(spec.trim())?;
+        columns.resize(columns.len() + multiplier, spec);
+    }
+    Some(columns)
+}
+
+/// Parses a single column specification of the form `[multiplier*][halign][.valign][width][style]`.
+fn parse_column_spec(spec: &str) -> Option<(usize, ColumnSpec)> {
+    let (multiplier, mut spec) = match spec.split_once('*') {
+        Some((multiplier, spec)) => (multiplier.parse().ok()?, spec),
+        None => (1, spec),
+    };
+    let mut column = ColumnSpec::default();
+
+    let mut strip = |prefix: &str| match spec.strip_prefix(prefix) {
+        Some(rest) => {
+            spec = rest;
+            true
+        }
+        None => false,
+    };
+    if strip("<") {
+        column.halign = HorizontalAlignment::Left;
+    } else if strip("^") {
+        column.halign = HorizontalAlignment::Center;
+    } else if strip(">") {
+        column.halign = HorizontalAlignment::Right;
+    }
+    if strip(".<") {
+        column.valign = VerticalAlignment::Top;
+    } else if strip(".^") {
+        column.valign = VerticalAlignment::Middle;
+    } else if strip(".>") {
+        column.valign = VerticalAlignment::Bottom;
+    }
+
+    let digits = spec.len() - spec.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 {
+        let width = spec[..digits].parse().ok()?;
+        spec = &spec[digits..];
+        column.width = match spec.strip_prefix('%') {
+            Some(rest) => {
+                spec = rest;
+                ColumnWidth::Percentage(width)
+            }
+            None => ColumnWidth::Proportional(width),
+        };
+    } else if let Some(rest) = spec.strip_prefix('~') {
+        spec = rest;
+        column.width = ColumnWidth::Auto;
+    }
+
+    let mut style = spec.chars();
+    column.style = match (style.next(), style.next()) {
+        (None, _) => ColumnStyle::Default,
+        (Some(c), None) => cell_style(c)?,
+        _ => return None,
+    };
+    Some((multiplier, column))
+}
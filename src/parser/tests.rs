@@ -91,7 +91,6 @@ fn parse_attr() {
     check_parse(
         super::parse_attribute,
         "foobar = \"14\"abc",
-        // FIXME
         expect![[r#"
             (
                 "foobar",
@@ -137,6 +136,42 @@ fn parse_attr_list() {
             }
         "#]],
     );
+    check_parse(
+        super::parse_attribute_list,
+        r#"[alt="a[0]"]"#,
+        expect![[r#"
+            {
+                "alt": Some(
+                    "a[0]",
+                ),
+            }
+        "#]],
+    );
+    check_parse(
+        super::parse_attribute_list,
+        r#"[title=foo\]bar]"#,
+        expect![[r#"
+            {
+                "title": Some(
+                    "foo\\]bar",
+                ),
+            }
+        "#]],
+    );
+    check_parse(
+        super::parse_attribute_list,
+        r#"[foo="14"abc,bar="baz"]"#,
+        expect![[r#"
+            {
+                "foo": Some(
+                    "\"14\"abc",
+                ),
+                "bar": Some(
+                    "baz",
+                ),
+            }
+        "#]],
+    );
 }
 
 #[test]
@@ -185,9 +220,18 @@ fn parse_doc_attribute() {
             DocAttribute {
                 id: "foo",
                 unset: true,
-                value: [
-                    "bar",
-                ],
+                value: [],
+            }
+        "#]],
+    );
+    check_parse(
+        super::parse_doc_attribute,
+        ":foo!: bar\n",
+        expect![[r#"
+            DocAttribute {
+                id: "foo",
+                unset: true,
+                value: [],
             }
         "#]],
     );
@@ -249,6 +293,77 @@ fn parse_doc_header() {
     );
 }
 
+#[test]
+fn parse_doc_header_leading_blank_lines() {
+    check_parse(
+        super::parse_doc_header,
+        "
+
+= Headline
+:attr:
+",
+        expect![[r#"
+            DocumentHeader {
+                title: "Headline",
+                author: None,
+                version: None,
+                attributes: [
+                    DocAttribute {
+                        id: "attr",
+                        unset: false,
+                        value: [],
+                    },
+                ],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_doc_header_author_and_revision() {
+    check_parse(
+        super::parse_doc_header,
+        "= Headline\nJane Doe <jane@example.org>; John Doe\nv1.0, 2021-01-31: First draft\n:attr:\n",
+        expect![[r#"
+            DocumentHeader {
+                title: "Headline",
+                author: Some(
+                    Author {
+                        full_name: "Jane Doe",
+                    },
+                ),
+                version: Some(
+                    Version {
+                        version: "v1.0",
+                        date: "2021-01-31",
+                    },
+                ),
+                attributes: [
+                    DocAttribute {
+                        id: "attr",
+                        unset: false,
+                        value: [],
+                    },
+                ],
+            }
+        "#]],
+    );
+    let revision = |line| super::parse_revision_line::<()>(Span::new(line)).unwrap().1;
+    expect![[r#"
+        (
+            Version {
+                version: "v2.1",
+                date: "",
+            },
+            Version {
+                version: "",
+                date: "2021-01-31",
+            },
+        )
+    "#]]
+    .assert_debug_eq(&(revision("v2.1"), revision("2021-01-31")));
+}
+
 #[test]
 fn parse_callouts() {
     check_parse(
@@ -288,7 +403,11 @@ fn parse_single_block_no_attributes() {
         expect![[r#"
             Block {
                 context: PageBreak,
+                title: None,
                 attributes: {},
+                id: None,
+                roles: [],
+                options: [],
                 callouts: [],
             }
         "#]],
@@ -299,7 +418,11 @@ fn parse_single_block_no_attributes() {
         expect![[r#"
             Block {
                 context: ThematicBreak,
+                title: None,
                 attributes: {},
+                id: None,
+                roles: [],
+                options: [],
                 callouts: [],
             }
         "#]],
@@ -315,7 +438,11 @@ with multiple lines
                 context: Listing(
                     "This is a listing block\nwith multiple lines\n",
                 ),
+                title: None,
                 attributes: {},
+                id: None,
+                roles: [],
+                options: [],
                 callouts: [],
             }
         "#]],
@@ -334,11 +461,3013 @@ with multiple lines
                                 "240",
                             ),
                         },
+                        positional: [
+                            None,
+                        ],
+                    },
+                ),
+                title: None,
+                attributes: {},
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_document_lossy() {
+    let (doc, errors) = super::parse_document_lossy(
+        r"The first paragraph.
+
+image::broken.png[width=240
+
+The second paragraph.
+",
+    );
+    expect![[r#"
+        Document {
+            header: None,
+            content: [
+                Block {
+                    context: Paragraph(
+                        "The first paragraph.",
+                    ),
+                    title: None,
+                    attributes: {},
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+                Block {
+                    context: Error(
+                        "image::broken.png[width=240\n",
+                    ),
+                    title: None,
+                    attributes: {},
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+                Block {
+                    context: Paragraph(
+                        "The second paragraph.",
+                    ),
+                    title: None,
+                    attributes: {},
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+            ],
+        }
+    "#]]
+    .assert_debug_eq(&doc);
+    expect![[r#"
+        [
+            InvalidBlock {
+                line: 3,
+                range: 22..50,
+            },
+        ]
+    "#]]
+    .assert_debug_eq(&errors);
+}
+
+#[test]
+fn parse_inline_passthrough() {
+    check_parse(
+        super::parse_inlines,
+        "pass:[]",
+        expect![[r#"
+            [
+                Passthrough {
+                    subs: [],
+                    content: "",
+                },
+            ]
+        "#]],
+    );
+    check_parse(
+        super::parse_inlines,
+        "The pass:c[(C)] symbol",
+        expect![[r#"
+            [
+                Text(
+                    "The ",
+                ),
+                Passthrough {
+                    subs: [
+                        SpecialCharacters,
+                    ],
+                    content: "(C)",
+                },
+                Text(
+                    " symbol",
+                ),
+            ]
+        "#]],
+    );
+    check_parse(
+        super::parse_inlines,
+        "pass:a[{attr}]",
+        expect![[r#"
+            [
+                Passthrough {
+                    subs: [
+                        Attributes,
+                    ],
+                    content: "{attr}",
+                },
+            ]
+        "#]],
+    );
+    check_parse(
+        super::parse_inlines,
+        "pass:a,q[*{attr}*] and +++<br>+++",
+        expect![[r#"
+            [
+                Passthrough {
+                    subs: [
+                        Attributes,
+                        Quotes,
+                    ],
+                    content: "*{attr}*",
+                },
+                Text(
+                    " and ",
+                ),
+                Passthrough {
+                    subs: [],
+                    content: "<br>",
+                },
+            ]
+        "#]],
+    );
+}
+
+#[test]
+fn parse_list() {
+    check_parse(
+        super::parse_list,
+        r"* foo
+continued
+** nested
+* [x] done
+
+. first
+. second
+",
+        expect![[r#"
+            List {
+                items: [
+                    ListItem {
+                        kind: Unordered,
+                        level: 1,
+                        paragraph: [
+                            Text(
+                                "foo\ncontinued",
+                            ),
+                        ],
+                        blocks: [
+                            Block {
+                                context: List(
+                                    List {
+                                        items: [
+                                            ListItem {
+                                                kind: Unordered,
+                                                level: 2,
+                                                paragraph: [
+                                                    Text(
+                                                        "nested",
+                                                    ),
+                                                ],
+                                                blocks: [],
+                                                bibliography: None,
+                                            },
+                                        ],
+                                        style: None,
+                                        start: None,
+                                    },
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                        ],
+                        bibliography: None,
+                    },
+                    ListItem {
+                        kind: Checklist(
+                            true,
+                        ),
+                        level: 1,
+                        paragraph: [
+                            Text(
+                                "done",
+                            ),
+                        ],
+                        blocks: [
+                            Block {
+                                context: List(
+                                    List {
+                                        items: [
+                                            ListItem {
+                                                kind: Ordered,
+                                                level: 2,
+                                                paragraph: [
+                                                    Text(
+                                                        "first",
+                                                    ),
+                                                ],
+                                                blocks: [],
+                                                bibliography: None,
+                                            },
+                                            ListItem {
+                                                kind: Ordered,
+                                                level: 2,
+                                                paragraph: [
+                                                    Text(
+                                                        "second",
+                                                    ),
+                                                ],
+                                                blocks: [],
+                                                bibliography: None,
+                                            },
+                                        ],
+                                        style: None,
+                                        start: None,
+                                    },
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                        ],
+                        bibliography: None,
+                    },
+                ],
+                style: None,
+                start: None,
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_description_list_styles() {
+    check_parse(
+        super::parse_attributed_block,
+        r"[horizontal]
+CPU:: The brain of the computer.
+Hard drive:: Permanent storage for operating system and/or user files.
+",
+        expect![[r#"
+            Block {
+                context: List(
+                    List {
+                        items: [
+                            ListItem {
+                                kind: Description(
+                                    [
+                                        Text(
+                                            "CPU",
+                                        ),
+                                    ],
+                                ),
+                                level: 1,
+                                paragraph: [],
+                                blocks: [
+                                    Block {
+                                        context: Paragraph(
+                                            "The brain of the computer.",
+                                        ),
+                                        title: None,
+                                        attributes: {},
+                                        id: None,
+                                        roles: [],
+                                        options: [],
+                                        callouts: [],
+                                    },
+                                ],
+                                bibliography: None,
+                            },
+                            ListItem {
+                                kind: Description(
+                                    [
+                                        Text(
+                                            "Hard drive",
+                                        ),
+                                    ],
+                                ),
+                                level: 1,
+                                paragraph: [],
+                                blocks: [
+                                    Block {
+                                        context: Paragraph(
+                                            "Permanent storage for operating system and/or user files.",
+                                        ),
+                                        title: None,
+                                        attributes: {},
+                                        id: None,
+                                        roles: [],
+                                        options: [],
+                                        callouts: [],
+                                    },
+                                ],
+                                bibliography: None,
+                            },
+                        ],
+                        style: Some(
+                            Horizontal,
+                        ),
+                        start: None,
+                    },
+                ),
+                title: None,
+                attributes: {
+                    "horizontal": None,
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+    check_parse(
+        super::parse_attributed_block,
+        r"[qanda]
+What is pagliascii?::
+  An AsciiDoc parser.
+Where can I find it?:: On GitHub.
+",
+        expect![[r#"
+            Block {
+                context: List(
+                    List {
+                        items: [
+                            ListItem {
+                                kind: Description(
+                                    [
+                                        Text(
+                                            "What is pagliascii?",
+                                        ),
+                                    ],
+                                ),
+                                level: 1,
+                                paragraph: [],
+                                blocks: [
+                                    Block {
+                                        context: Paragraph(
+                                            "An AsciiDoc parser.",
+                                        ),
+                                        title: None,
+                                        attributes: {},
+                                        id: None,
+                                        roles: [],
+                                        options: [],
+                                        callouts: [],
+                                    },
+                                ],
+                                bibliography: None,
+                            },
+                            ListItem {
+                                kind: Description(
+                                    [
+                                        Text(
+                                            "Where can I find it?",
+                                        ),
+                                    ],
+                                ),
+                                level: 1,
+                                paragraph: [],
+                                blocks: [
+                                    Block {
+                                        context: Paragraph(
+                                            "On GitHub.",
+                                        ),
+                                        title: None,
+                                        attributes: {},
+                                        id: None,
+                                        roles: [],
+                                        options: [],
+                                        callouts: [],
+                                    },
+                                ],
+                                bibliography: None,
+                            },
+                        ],
+                        style: Some(
+                            Qanda,
+                        ),
+                        start: None,
+                    },
+                ),
+                title: None,
+                attributes: {
+                    "qanda": None,
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_multi_paragraph_list_item() {
+    check_parse(
+        super::parse_list,
+        r"* The first paragraph
+of the first item.
++
+The second paragraph.
+* The second item.
+
+  An indented paragraph.
+",
+        expect![[r#"
+            List {
+                items: [
+                    ListItem {
+                        kind: Unordered,
+                        level: 1,
+                        paragraph: [
+                            Text(
+                                "The first paragraph\nof the first item.",
+                            ),
+                        ],
+                        blocks: [
+                            Block {
+                                context: Paragraph(
+                                    "The second paragraph.",
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                        ],
+                        bibliography: None,
                     },
+                    ListItem {
+                        kind: Unordered,
+                        level: 1,
+                        paragraph: [
+                            Text(
+                                "The second item.",
+                            ),
+                        ],
+                        blocks: [
+                            Block {
+                                context: Paragraph(
+                                    "An indented paragraph.",
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                        ],
+                        bibliography: None,
+                    },
+                ],
+                style: None,
+                start: None,
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_lead_paragraph() {
+    check_parse(
+        super::parse_attributed_block,
+        r"[.lead]
+This is the ultimate paragraph.
+",
+        expect![[r#"
+            Block {
+                context: Paragraph(
+                    "This is the ultimate paragraph.",
                 ),
+                title: None,
                 attributes: {},
+                id: None,
+                roles: [
+                    "lead",
+                ],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_multi_role_paragraph() {
+    check_parse(
+        super::parse_attributed_block,
+        r"[normal#intro.text-center.big, title=Intro]
+A centered paragraph.
+",
+        expect![[r#"
+            Block {
+                context: Paragraph(
+                    "A centered paragraph.",
+                ),
+                title: None,
+                attributes: {
+                    "normal": None,
+                    "title": Some(
+                        "Intro",
+                    ),
+                },
+                id: Some(
+                    "intro",
+                ),
+                roles: [
+                    "text-center",
+                    "big",
+                ],
+                options: [],
                 callouts: [],
             }
         "#]],
     );
 }
+
+#[test]
+fn parse_nested_formatting() {
+    check_parse(
+        super::parse_inlines,
+        "*bold _italic_ bold*",
+        expect![[r#"
+            [
+                Format(
+                    Bold,
+                    [
+                        Text(
+                            "bold ",
+                        ),
+                        Format(
+                            Italic,
+                            [
+                                Text(
+                                    "italic",
+                                ),
+                            ],
+                        ),
+                        Text(
+                            " bold",
+                        ),
+                    ],
+                ),
+            ]
+        "#]],
+    );
+}
+
+#[test]
+fn parse_monospace_is_literal() {
+    check_parse(
+        super::parse_inlines,
+        "`*literal asterisks*` and *bold*",
+        expect![[r#"
+            [
+                Format(
+                    Monospace,
+                    [
+                        Text(
+                            "*literal asterisks*",
+                        ),
+                    ],
+                ),
+                Text(
+                    " and ",
+                ),
+                Format(
+                    Bold,
+                    [
+                        Text(
+                            "bold",
+                        ),
+                    ],
+                ),
+            ]
+        "#]],
+    );
+}
+
+#[test]
+fn parse_italic_with_apostrophe() {
+    check_parse(
+        super::parse_inlines,
+        "_it's_ not snake_case_",
+        expect![[r#"
+            [
+                Format(
+                    Italic,
+                    [
+                        Text(
+                            "it's",
+                        ),
+                    ],
+                ),
+                Text(
+                    " not snake_case_",
+                ),
+            ]
+        "#]],
+    );
+}
+
+#[test]
+fn block_parser() {
+    let mut blocks = super::BlockParser::new(Span::new(
+        r"The first paragraph.
+
+'''
+
+* an item
+",
+    ));
+    for _ in 0..3 {
+        assert!(matches!(blocks.next(), Some(Ok(_))));
+    }
+    assert_eq!(blocks.next(), None);
+}
+
+#[test]
+fn parse_nested_sections() {
+    check_parse(
+        super::parse_blocks,
+        r"== First
+
+=== Nested
+
+Nested content.
+
+== Second
+",
+        expect![[r#"
+            [
+                Block {
+                    context: SectionTitle(
+                        SectionTitle {
+                            level: 1,
+                            content: "First",
+                            style: None,
+                        },
+                        [
+                            Block {
+                                context: SectionTitle(
+                                    SectionTitle {
+                                        level: 2,
+                                        content: "Nested",
+                                        style: None,
+                                    },
+                                    [
+                                        Block {
+                                            context: Paragraph(
+                                                "Nested content.",
+                                            ),
+                                            title: None,
+                                            attributes: {},
+                                            id: None,
+                                            roles: [],
+                                            options: [],
+                                            callouts: [],
+                                        },
+                                    ],
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                        ],
+                    ),
+                    title: None,
+                    attributes: {},
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+                Block {
+                    context: SectionTitle(
+                        SectionTitle {
+                            level: 1,
+                            content: "Second",
+                            style: None,
+                        },
+                        [],
+                    ),
+                    title: None,
+                    attributes: {},
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+            ]
+        "#]],
+    );
+}
+
+#[test]
+fn parse_tab_indented_literal() {
+    let input = "\tfirst line\n\t  second line\n";
+    check_parse(
+        super::parse_attributed_block,
+        input,
+        expect![[r#"
+            Block {
+                context: Literal(
+                    "\tfirst line\n\t  second line\n",
+                ),
+                title: None,
+                attributes: {},
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+    let (_, literal) = super::parse_literal_paragraph::<()>(Span::new(input)).unwrap();
+    expect![[r#"
+        [
+            "first line",
+            "  second line",
+        ]
+    "#]]
+    .assert_debug_eq(&super::dedent_lines(literal, 4));
+}
+
+#[test]
+fn parse_mixed_indented_literal() {
+    let input = Span::new("    four spaces\n\ta tab\n  \ttwo spaces and a tab\n     five spaces\n");
+    let (_, literal) = super::parse_literal_paragraph::<()>(input).unwrap();
+    expect![[r#"
+        [
+            "four spaces",
+            "a tab",
+            "two spaces and a tab",
+            " five spaces",
+        ]
+    "#]]
+    .assert_debug_eq(&super::dedent_lines(literal, 4));
+}
+
+#[test]
+fn parse_passthrough_block_subs() {
+    let parse = |input| super::parse_attributed_block::<()>(Span::new(input)).unwrap().1;
+    let block = parse("[subs=attributes]\n++++\n<b>{author}</b>\n++++\n");
+    assert_eq!(block.context, crate::ast::Context::Passthrough("<b>{author}</b>\n".into()));
+    assert_eq!(block.subs(), [crate::ast::Substitution::Attributes]);
+
+    // passthrough blocks apply no substitutions by default
+    assert_eq!(parse("++++\n{author}\n++++\n").subs(), []);
+    assert_eq!(parse("[subs=none]\n++++\n{author}\n++++\n").subs(), []);
+}
+
+#[test]
+fn parse_ordered_list_styles() {
+    let list = |input| match super::parse_attributed_block::<()>(Span::new(input)) {
+        Ok((_, crate::ast::Block { context: crate::ast::Context::List(list), .. })) => list,
+        res => panic!("expected a list, got {:?}", res),
+    };
+    let alpha = list("[loweralpha]\n. one\n. two\n");
+    assert_eq!((alpha.style, alpha.start), (Some(crate::ast::ListStyle::LowerAlpha), None));
+    assert_eq!(alpha.items.len(), 2);
+    assert_eq!(alpha.style.and_then(crate::ast::ListStyle::html_type), Some("a"));
+
+    let arabic = list("[arabic,start=5]\n. five\n. six\n");
+    assert_eq!((arabic.style, arabic.start), (Some(crate::ast::ListStyle::Arabic), Some(5)));
+}
+
+#[test]
+fn parse_block_options() {
+    let parse = |input| super::parse_attributed_block::<()>(Span::new(input)).unwrap().1;
+    let listing = parse("[source%nowrap%linenums,rust]\n----\nfn main() {}\n----\n");
+    assert_eq!(listing.options(), [Span::new("nowrap"), Span::new("linenums")]);
+    assert!(listing.has_option("nowrap"));
+    assert!(listing.has_option("linenums"));
+    assert!(!listing.has_option("unbreakable"));
+
+    let example = parse("[%unbreakable,options=\"autofit, nowrap\"]\n====\nKeep together.\n====\n");
+    assert_eq!(example.options(), [Span::new("unbreakable")]);
+    assert!(example.has_option("unbreakable"));
+    assert!(example.has_option("nowrap"));
+    assert!(example.has_option("autofit"));
+}
+
+#[test]
+fn parse_verse() {
+    check_parse(
+        super::parse_blocks,
+        r"[verse, Carl Sandburg, Two Nocturnes]
+____
+The fog comes
+on little cat feet.
+
+It sits looking
+over harbor and city
+____
+
+[verse,citation=Fog]
+Silent haunches
+and then moves on.
+",
+        expect![[r#"
+            [
+                Block {
+                    context: Verse(
+                        Verse {
+                            content: "The fog comes\non little cat feet.\n\nIt sits looking\nover harbor and city",
+                            attribution: Some(
+                                "Carl Sandburg",
+                            ),
+                            citation: Some(
+                                "Two Nocturnes",
+                            ),
+                        },
+                    ),
+                    title: None,
+                    attributes: {
+                        "verse": None,
+                        "Carl Sandburg": None,
+                        "Two Nocturnes": None,
+                    },
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+                Block {
+                    context: Verse(
+                        Verse {
+                            content: "Silent haunches\nand then moves on.",
+                            attribution: None,
+                            citation: Some(
+                                "Fog",
+                            ),
+                        },
+                    ),
+                    title: None,
+                    attributes: {
+                        "verse": None,
+                        "citation": Some(
+                            "Fog",
+                        ),
+                    },
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+            ]
+        "#]],
+    );
+
+    let input = "Intro.\n\n[verse, Carl Sandburg, citation=Fog]\nSilent haunches\n";
+    let (_, blocks) = super::parse_blocks::<()>(Span::new(input)).unwrap();
+    match &blocks[1].context {
+        crate::ast::Context::Verse(verse) => {
+            let attribution = verse.attribution.unwrap();
+            let citation = verse.citation.unwrap();
+            assert_eq!(&input[attribution.byte_range()], "Carl Sandburg");
+            assert_eq!(&input[citation.byte_range()], "Fog");
+        }
+        context => panic!("expected a verse, got {:?}", context),
+    }
+}
+
+#[test]
+fn parse_paragraph_admonition() {
+    check_parse(
+        super::parse_blocks,
+        r"[TIP]
+Remember to save.
+
+[WARNING]
+Mind the
+gap.
+
+Not an admonition.
+",
+        expect![[r#"
+            [
+                Block {
+                    context: Admonition {
+                        label: "TIP",
+                        blocks: [
+                            Block {
+                                context: Paragraph(
+                                    "Remember to save.",
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                        ],
+                    },
+                    title: None,
+                    attributes: {
+                        "TIP": None,
+                    },
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+                Block {
+                    context: Admonition {
+                        label: "WARNING",
+                        blocks: [
+                            Block {
+                                context: Paragraph(
+                                    "Mind the\ngap.",
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                        ],
+                    },
+                    title: None,
+                    attributes: {
+                        "WARNING": None,
+                    },
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+                Block {
+                    context: Paragraph(
+                        "Not an admonition.",
+                    ),
+                    title: None,
+                    attributes: {},
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+            ]
+        "#]],
+    );
+}
+
+#[test]
+fn parse_delimited_admonition() {
+    check_parse(
+        super::parse_attributed_block,
+        r"[NOTE]
+====
+An admonition spanning multiple blocks.
+
+* with a list
+====
+",
+        expect![[r#"
+            Block {
+                context: Admonition {
+                    label: "NOTE",
+                    blocks: [
+                        Block {
+                            context: Paragraph(
+                                "An admonition spanning multiple blocks.",
+                            ),
+                            title: None,
+                            attributes: {},
+                            id: None,
+                            roles: [],
+                            options: [],
+                            callouts: [],
+                        },
+                        Block {
+                            context: List(
+                                List {
+                                    items: [
+                                        ListItem {
+                                            kind: Unordered,
+                                            level: 1,
+                                            paragraph: [
+                                                Text(
+                                                    "with a list",
+                                                ),
+                                            ],
+                                            blocks: [],
+                                            bibliography: None,
+                                        },
+                                    ],
+                                    style: None,
+                                    start: None,
+                                },
+                            ),
+                            title: None,
+                            attributes: {},
+                            id: None,
+                            roles: [],
+                            options: [],
+                            callouts: [],
+                        },
+                    ],
+                },
+                title: None,
+                attributes: {
+                    "NOTE": None,
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_table_column_alignments() {
+    check_parse(
+        super::parse_attributed_block,
+        r#"[cols="<,^,>"]
+|===
+|Left |Center |Right
+
+|1 |2 |3
+|===
+"#,
+        expect![[r#"
+            Block {
+                context: Table(
+                    Table {
+                        columns: [
+                            ColumnSpec {
+                                width: Proportional(
+                                    1,
+                                ),
+                                halign: Left,
+                                valign: Top,
+                                style: Default,
+                            },
+                            ColumnSpec {
+                                width: Proportional(
+                                    1,
+                                ),
+                                halign: Center,
+                                valign: Top,
+                                style: Default,
+                            },
+                            ColumnSpec {
+                                width: Proportional(
+                                    1,
+                                ),
+                                halign: Right,
+                                valign: Top,
+                                style: Default,
+                            },
+                        ],
+                        header: Some(
+                            [
+                                TableCell {
+                                    content: "Left",
+                                    style: None,
+                                },
+                                TableCell {
+                                    content: "Center",
+                                    style: None,
+                                },
+                                TableCell {
+                                    content: "Right",
+                                    style: None,
+                                },
+                            ],
+                        ),
+                        rows: [
+                            [
+                                TableCell {
+                                    content: "1",
+                                    style: None,
+                                },
+                                TableCell {
+                                    content: "2",
+                                    style: None,
+                                },
+                                TableCell {
+                                    content: "3",
+                                    style: None,
+                                },
+                            ],
+                        ],
+                        footer: None,
+                        autowidth: false,
+                        width: None,
+                        frame: All,
+                        grid: All,
+                    },
+                ),
+                title: None,
+                attributes: {
+                    "cols": Some(
+                        "<,^,>",
+                    ),
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_table_column_styles() {
+    check_parse(
+        super::parse_attributed_block,
+        r#"[cols="1a,2h"]
+|===
+|* a list
+|Header
+|===
+"#,
+        expect![[r#"
+            Block {
+                context: Table(
+                    Table {
+                        columns: [
+                            ColumnSpec {
+                                width: Proportional(
+                                    1,
+                                ),
+                                halign: Left,
+                                valign: Top,
+                                style: AsciiDoc,
+                            },
+                            ColumnSpec {
+                                width: Proportional(
+                                    2,
+                                ),
+                                halign: Left,
+                                valign: Top,
+                                style: Header,
+                            },
+                        ],
+                        header: None,
+                        rows: [
+                            [
+                                TableCell {
+                                    content: "* a list",
+                                    style: None,
+                                },
+                                TableCell {
+                                    content: "Header",
+                                    style: None,
+                                },
+                            ],
+                        ],
+                        footer: None,
+                        autowidth: false,
+                        width: None,
+                        frame: All,
+                        grid: All,
+                    },
+                ),
+                title: None,
+                attributes: {
+                    "cols": Some(
+                        "1a,2h",
+                    ),
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_cols() {
+    expect![[r#"
+        Some(
+            [
+                ColumnSpec {
+                    width: Proportional(
+                        2,
+                    ),
+                    halign: Center,
+                    valign: Bottom,
+                    style: Monospace,
+                },
+                ColumnSpec {
+                    width: Auto,
+                    halign: Left,
+                    valign: Top,
+                    style: Emphasis,
+                },
+                ColumnSpec {
+                    width: Auto,
+                    halign: Left,
+                    valign: Top,
+                    style: Emphasis,
+                },
+                ColumnSpec {
+                    width: Percentage(
+                        25,
+                    ),
+                    halign: Left,
+                    valign: Top,
+                    style: Default,
+                },
+            ],
+        )
+    "#]]
+    .assert_debug_eq(&super::parse_cols("^.>2m,2*~e,25%"));
+}
+
+#[test]
+fn parse_comments() {
+    let input = r"// a line comment
+A paragraph.
+
+////
+A comment block.
+////
+";
+    let (doc, errors) = super::parse_document_lossy(input);
+    assert!(errors.is_empty());
+    expect![[r#"
+        [
+            Block {
+                context: Paragraph(
+                    "A paragraph.",
+                ),
+                title: None,
+                attributes: {},
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            },
+        ]
+    "#]]
+    .assert_debug_eq(&doc.content);
+
+    let options = super::ParseOptions { preserve_comments: true, ..Default::default() };
+    let (doc, errors) = super::parse_document_with(input, options);
+    assert!(errors.is_empty());
+    expect![[r#"
+        [
+            Block {
+                context: Comment(
+                    " a line comment",
+                ),
+                title: None,
+                attributes: {},
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            },
+            Block {
+                context: Paragraph(
+                    "A paragraph.",
+                ),
+                title: None,
+                attributes: {},
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            },
+            Block {
+                context: Comment(
+                    "A comment block.\n",
+                ),
+                title: None,
+                attributes: {},
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            },
+        ]
+    "#]]
+    .assert_debug_eq(&doc.content);
+}
+
+#[test]
+fn parse_comment_lines() {
+    // skipping a long run of comments must not grow the stack
+    let input = "// comment\n".repeat(100_000);
+    assert_eq!(super::BlockParser::new(Span::new(&input)).count(), 0);
+
+    // a line starting with three slashes is not a comment
+    let blocks: Vec<_> = super::BlockParser::new(Span::new("/// text\n////text\n"))
+        .map(|block| block.unwrap().context)
+        .collect();
+    expect![[r#"
+        [
+            Paragraph(
+                "/// text\n////text",
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&blocks);
+}
+
+#[test]
+fn parse_hardbreaks_paragraph() {
+    let input = Span::new(
+        r"[%hardbreaks]
+Roses are red,
+violets are *blue*.
+",
+    );
+    let (_, block) = super::parse_attributed_block::<()>(input).unwrap();
+    let hardbreaks = block.options.iter().any(|option| option.text() == "hardbreaks");
+    let paragraph = match block.context {
+        crate::ast::Context::Paragraph(paragraph) => paragraph,
+        _ => unreachable!(),
+    };
+    expect![[r#"
+        [
+            Text(
+                "Roses are red,",
+            ),
+            LineBreak,
+            Text(
+                "violets are ",
+            ),
+            Format(
+                Bold,
+                [
+                    Text(
+                        "blue",
+                    ),
+                ],
+            ),
+            Text(
+                ".",
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&super::parse_paragraph_inlines::<()>(paragraph, hardbreaks).unwrap().1);
+    expect![[r#"
+        [
+            Text(
+                "Roses are red,\nviolets are ",
+            ),
+            Format(
+                Bold,
+                [
+                    Text(
+                        "blue",
+                    ),
+                ],
+            ),
+            Text(
+                ".",
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&super::parse_paragraph_inlines::<()>(paragraph, false).unwrap().1);
+}
+
+#[test]
+fn parse_hardbreaks_document() {
+    let texts = |input| {
+        let (doc, _) = super::parse_document_lossy(input);
+        let mut blocks = Vec::new();
+        for block in doc.content {
+            match block.context {
+                crate::ast::Context::Example(nested) => blocks.extend(nested),
+                _ => blocks.push(block),
+            }
+        }
+        blocks.iter().map(super::parse_block_text).collect::<Vec<_>>()
+    };
+    expect![[r#"
+        [
+            Some(
+                [
+                    Text(
+                        "a",
+                    ),
+                    LineBreak,
+                    Text(
+                        "b",
+                    ),
+                ],
+            ),
+            Some(
+                [
+                    Text(
+                        "c\nd",
+                    ),
+                ],
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&texts("[%hardbreaks]\na\nb\n\nc\nd\n"));
+    expect![[r#"
+        [
+            Some(
+                [
+                    Text(
+                        "a",
+                    ),
+                    LineBreak,
+                    Text(
+                        "b",
+                    ),
+                ],
+            ),
+            Some(
+                [
+                    Text(
+                        "c",
+                    ),
+                    LineBreak,
+                    Text(
+                        "d",
+                    ),
+                ],
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&texts("= Title\n:hardbreaks-option:\n\na\nb\n\n====\nc\nd\n====\n"));
+}
+
+#[test]
+fn parse_block_title_before_attributes() {
+    check_parse(
+        super::parse_attributed_block,
+        r"
+.My Listing
+[source,rust]
+```
+fn main() {}
+```
+",
+        expect![[r#"
+            Block {
+                context: Listing(
+                    "fn main() {}\n",
+                ),
+                title: Some(
+                    "My Listing",
+                ),
+                attributes: {
+                    "source": None,
+                    "rust": None,
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_block_title_after_attributes() {
+    check_parse(
+        super::parse_attributed_block,
+        r"[source,rust]
+.My Listing
+```
+fn main() {}
+```
+",
+        expect![[r#"
+            Block {
+                context: Listing(
+                    "fn main() {}\n",
+                ),
+                title: Some(
+                    "My Listing",
+                ),
+                attributes: {
+                    "source": None,
+                    "rust": None,
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_inline_stem() {
+    check_parse(
+        super::parse_inlines,
+        "The square stem:[x^2] and latexmath:[\\sqrt{4}]",
+        expect![[r#"
+            [
+                Text(
+                    "The square ",
+                ),
+                Stem {
+                    notation: None,
+                    content: "x^2",
+                },
+                Text(
+                    " and ",
+                ),
+                Stem {
+                    notation: Some(
+                        LatexMath,
+                    ),
+                    content: "\\sqrt{4}",
+                },
+            ]
+        "#]],
+    );
+}
+
+#[test]
+fn parse_latexmath_block() {
+    check_parse(
+        super::parse_attributed_block,
+        r"[latexmath]
+++++
+\sqrt{4} = 2
+++++
+",
+        expect![[r#"
+            Block {
+                context: Stem {
+                    notation: Some(
+                        LatexMath,
+                    ),
+                    content: "\\sqrt{4} = 2\n",
+                },
+                title: None,
+                attributes: {
+                    "latexmath": None,
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_block_text_default_subs() {
+    let input = Span::new("```\nlet *not_bold* = 1;\n```\n\nSome *bold* text.\n");
+    let (_, blocks) = super::parse_blocks::<()>(input).unwrap();
+    let texts: Vec<_> = blocks.iter().map(super::parse_block_text).collect();
+    expect![[r#"
+        [
+            Some(
+                [
+                    Text(
+                        "let *not_bold* = 1;",
+                    ),
+                ],
+            ),
+            Some(
+                [
+                    Text(
+                        "Some ",
+                    ),
+                    Format(
+                        Bold,
+                        [
+                            Text(
+                                "bold",
+                            ),
+                        ],
+                    ),
+                    Text(
+                        " text.",
+                    ),
+                ],
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&texts);
+}
+
+#[test]
+fn parse_block_text_subs() {
+    let input = Span::new(
+        "[subs=quotes]\n----\nlet *bold* = 1;\n----\n\n[subs=attributes]\n++++\n<b>*raw*</b>\n++++\n\n[subs=none]\nNot *bold*.\n",
+    );
+    let (_, blocks) = super::parse_blocks::<()>(input).unwrap();
+    let texts: Vec<_> = blocks.iter().map(super::parse_block_text).collect();
+    expect![[r#"
+        [
+            Some(
+                [
+                    Text(
+                        "let ",
+                    ),
+                    Format(
+                        Bold,
+                        [
+                            Text(
+                                "bold",
+                            ),
+                        ],
+                    ),
+                    Text(
+                        " = 1;",
+                    ),
+                ],
+            ),
+            Some(
+                [
+                    Text(
+                        "<b>*raw*</b>",
+                    ),
+                ],
+            ),
+            Some(
+                [
+                    Text(
+                        "Not *bold*.",
+                    ),
+                ],
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&texts);
+}
+
+#[test]
+fn parse_discrete_heading() {
+    check_parse(
+        super::parse_blocks,
+        r"== Section
+
+[discrete]
+=== A Heading
+
+Still part of the section.
+",
+        expect![[r#"
+            [
+                Block {
+                    context: SectionTitle(
+                        SectionTitle {
+                            level: 1,
+                            content: "Section",
+                            style: None,
+                        },
+                        [
+                            Block {
+                                context: SectionTitle(
+                                    SectionTitle {
+                                        level: 2,
+                                        content: "A Heading",
+                                        style: Some(
+                                            Discrete,
+                                        ),
+                                    },
+                                    [],
+                                ),
+                                title: None,
+                                attributes: {
+                                    "discrete": None,
+                                },
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                            Block {
+                                context: Paragraph(
+                                    "Still part of the section.",
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                        ],
+                    ),
+                    title: None,
+                    attributes: {},
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+            ]
+        "#]],
+    );
+}
+
+#[test]
+fn parse_appendix_section() {
+    check_parse(
+        super::parse_attributed_block,
+        r"[appendix]
+== Additional Resources
+
+Some resources.
+",
+        expect![[r#"
+            Block {
+                context: SectionTitle(
+                    SectionTitle {
+                        level: 1,
+                        content: "Additional Resources",
+                        style: Some(
+                            Appendix,
+                        ),
+                    },
+                    [
+                        Block {
+                            context: Paragraph(
+                                "Some resources.",
+                            ),
+                            title: None,
+                            attributes: {},
+                            id: None,
+                            roles: [],
+                            options: [],
+                            callouts: [],
+                        },
+                    ],
+                ),
+                title: None,
+                attributes: {
+                    "appendix": None,
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_paragraph_trailing_whitespace() {
+    check_parse(
+        super::parse_paragraph,
+        "Some text.   \nMore text.  \t\n\nNext",
+        expect![[r#"
+            "Some text.   \nMore text."
+        "#]],
+    );
+    check_parse(
+        super::parse_paragraph,
+        "No newline at the end",
+        expect![[r#"
+            "No newline at the end"
+        "#]],
+    );
+
+    let (_, paragraph) = super::parse_paragraph::<()>(Span::new("Some text.  \n")).unwrap();
+    assert_eq!(paragraph.location_offset(), 0);
+    assert_eq!(paragraph.len(), "Some text.".len());
+}
+
+#[test]
+fn parse_table_autowidth() {
+    check_parse(
+        super::parse_attributed_block,
+        r#"[%autowidth,width=75%]
+|===
+|A |B
+|===
+"#,
+        expect![[r#"
+            Block {
+                context: Table(
+                    Table {
+                        columns: [
+                            ColumnSpec {
+                                width: Proportional(
+                                    1,
+                                ),
+                                halign: Left,
+                                valign: Top,
+                                style: Default,
+                            },
+                            ColumnSpec {
+                                width: Proportional(
+                                    1,
+                                ),
+                                halign: Left,
+                                valign: Top,
+                                style: Default,
+                            },
+                        ],
+                        header: None,
+                        rows: [
+                            [
+                                TableCell {
+                                    content: "A",
+                                    style: None,
+                                },
+                                TableCell {
+                                    content: "B",
+                                    style: None,
+                                },
+                            ],
+                        ],
+                        footer: None,
+                        autowidth: true,
+                        width: Some(
+                            "75%",
+                        ),
+                        frame: All,
+                        grid: All,
+                    },
+                ),
+                title: None,
+                attributes: {
+                    "width": Some(
+                        "75%",
+                    ),
+                },
+                id: None,
+                roles: [],
+                options: [
+                    "autowidth",
+                ],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_table_frame_and_grid() {
+    check_parse(
+        super::parse_attributed_block,
+        r#"[frame=topbot,grid=rows]
+|===
+|A |B
+|===
+"#,
+        expect![[r#"
+            Block {
+                context: Table(
+                    Table {
+                        columns: [
+                            ColumnSpec {
+                                width: Proportional(
+                                    1,
+                                ),
+                                halign: Left,
+                                valign: Top,
+                                style: Default,
+                            },
+                            ColumnSpec {
+                                width: Proportional(
+                                    1,
+                                ),
+                                halign: Left,
+                                valign: Top,
+                                style: Default,
+                            },
+                        ],
+                        header: None,
+                        rows: [
+                            [
+                                TableCell {
+                                    content: "A",
+                                    style: None,
+                                },
+                                TableCell {
+                                    content: "B",
+                                    style: None,
+                                },
+                            ],
+                        ],
+                        footer: None,
+                        autowidth: false,
+                        width: None,
+                        frame: Ends,
+                        grid: Rows,
+                    },
+                ),
+                title: None,
+                attributes: {
+                    "frame": Some(
+                        "topbot",
+                    ),
+                    "grid": Some(
+                        "rows",
+                    ),
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_table_cells_with_pipes() {
+    let input = "|===\n|`a | b` |a \\| b\n|+c | d+ |+++e | f+++\n|`unclosed |g\n|===\n";
+    let table = match super::parse_attributed_block::<()>(Span::new(input)) {
+        Ok((_, crate::ast::Block { context: crate::ast::Context::Table(table), .. })) => table,
+        res => panic!("expected a table, got {:?}", res),
+    };
+    let rows: Vec<Vec<_>> =
+        table.rows.iter().map(|row| row.iter().map(|cell| cell.content.text()).collect()).collect();
+    assert_eq!(rows, [["`a | b`", "a \\| b"], ["+c | d+", "+++e | f+++"], ["`unclosed", "g"]]);
+    assert_eq!(table.rows[0][1].text(), "a | b");
+
+    // a `+` or backtick that doesn't start a constrained pair doesn't enclose anything
+    let input = "|===\n|1 + 2 | 3 + 4\n|a`b |c`d\n|===\n";
+    let table = match super::parse_attributed_block::<()>(Span::new(input)) {
+        Ok((_, crate::ast::Block { context: crate::ast::Context::Table(table), .. })) => table,
+        res => panic!("expected a table, got {:?}", res),
+    };
+    let rows: Vec<Vec<_>> =
+        table.rows.iter().map(|row| row.iter().map(|cell| cell.content.text()).collect()).collect();
+    assert_eq!(rows, [["1 + 2", "3 + 4"], ["a`b", "c`d"]]);
+}
+
+#[test]
+fn parse_table_header_and_footer_options() {
+    let table = |input| match super::parse_attributed_block::<()>(Span::new(input)) {
+        Ok((_, crate::ast::Block { context: crate::ast::Context::Table(table), .. })) => table,
+        res => panic!("expected a table, got {:?}", res),
+    };
+    let legacy = table("[options=\"header,footer\"]\n|===\n|A |B\n|1 |2\n|C |D\n|===\n");
+    let shorthand = table("[%header%footer]\n|===\n|A |B\n|1 |2\n|C |D\n|===\n");
+    assert_eq!(legacy, shorthand);
+    expect![[r#"
+        (
+            Some(
+                [
+                    TableCell {
+                        content: "A",
+                        style: None,
+                    },
+                    TableCell {
+                        content: "B",
+                        style: None,
+                    },
+                ],
+            ),
+            [
+                [
+                    TableCell {
+                        content: "1",
+                        style: None,
+                    },
+                    TableCell {
+                        content: "2",
+                        style: None,
+                    },
+                ],
+            ],
+            Some(
+                [
+                    TableCell {
+                        content: "C",
+                        style: None,
+                    },
+                    TableCell {
+                        content: "D",
+                        style: None,
+                    },
+                ],
+            ),
+        )
+    "#]]
+    .assert_debug_eq(&(legacy.header, legacy.rows, legacy.footer));
+
+    // an implicit header row is kept as is
+    let implicit = table("[%header]\n|===\n|A |B\n\n|1 |2\n|===\n");
+    assert_eq!(implicit.header.map(|header| header.len()), Some(2));
+    assert_eq!(implicit.rows.len(), 1);
+}
+
+#[test]
+fn parse_doc_require_header() {
+    let options = super::ParseOptions { require_header: true, ..Default::default() };
+    let input = Span::new("A fragment without a header.\n");
+    assert!(super::parse_doc_with::<()>(input, options).is_err());
+    let (_, errors) = super::parse_document_with("A fragment without a header.\n", options);
+    expect![[r#"
+        [
+            MissingHeader,
+        ]
+    "#]]
+    .assert_debug_eq(&errors);
+}
+
+#[test]
+fn parse_doc_header_only() {
+    let options = super::ParseOptions { header_only: true, ..Default::default() };
+    check_parse(
+        |i| super::parse_doc_with(i, options),
+        "= Document Title\n:toc:\n\nThe content.\n",
+        expect![[r#"
+            Document {
+                header: Some(
+                    DocumentHeader {
+                        title: "Document Title",
+                        author: None,
+                        version: None,
+                        attributes: [
+                            DocAttribute {
+                                id: "toc",
+                                unset: false,
+                                value: [],
+                            },
+                        ],
+                    },
+                ),
+                content: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_doc_forbid_header() {
+    let options = super::ParseOptions { forbid_header: true, ..Default::default() };
+    let (doc, errors) = super::parse_document_with("= Not a Header\n\nThe content.\n", options);
+    assert!(errors.is_empty());
+    expect![[r#"
+        Document {
+            header: None,
+            content: [
+                Block {
+                    context: SectionTitle(
+                        SectionTitle {
+                            level: 0,
+                            content: "Not a Header",
+                            style: None,
+                        },
+                        [
+                            Block {
+                                context: Paragraph(
+                                    "The content.",
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                        ],
+                    ),
+                    title: None,
+                    attributes: {},
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+            ],
+        }
+    "#]]
+    .assert_debug_eq(&doc);
+}
+
+#[test]
+fn parse_block_metadata_any_order() {
+    let lines = ["[[intro,Introduction]]\n", ".The Title\n", "[quote.lead%rotate]\n"];
+    let orders = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+    let inputs: Vec<String> = orders
+        .iter()
+        .map(|order| order.iter().map(|&idx| lines[idx]).chain(Some("A paragraph.\n")).collect())
+        .collect();
+    let blocks: Vec<_> = inputs
+        .iter()
+        .map(|input| super::parse_attributed_block::<()>(Span::new(input)).expect("block").1)
+        .collect();
+    assert!(blocks.iter().all(|block| *block == blocks[0]));
+    expect![[r#"
+        Block {
+            context: Paragraph(
+                "A paragraph.",
+            ),
+            title: Some(
+                "The Title",
+            ),
+            attributes: {
+                "quote": None,
+                "reftext": Some(
+                    "Introduction",
+                ),
+            },
+            id: Some(
+                "intro",
+            ),
+            roles: [
+                "lead",
+            ],
+            options: [
+                "rotate",
+            ],
+            callouts: [],
+        }
+    "#]]
+    .assert_debug_eq(&blocks[0]);
+}
+
+#[test]
+fn parse_doc_doctype_title() {
+    let input = "NAME\n\nprogram - does things\n";
+    let manpage =
+        super::ParseOptions { doctype: Some(crate::ast::Doctype::Manpage), ..Default::default() };
+    assert!(super::parse_doc_with::<()>(Span::new(input), manpage).is_err());
+    let (_, errors) = super::parse_document_with(input, manpage);
+    expect![[r#"
+        [
+            MissingDocumentTitle {
+                doctype: Manpage,
+            },
+        ]
+    "#]]
+    .assert_debug_eq(&errors);
+
+    let book =
+        super::ParseOptions { doctype: Some(crate::ast::Doctype::Book), ..Default::default() };
+    assert!(super::parse_doc_with::<()>(Span::new(input), book).is_ok());
+    let (_, errors) = super::parse_document_with(input, book);
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn parse_mixed_nested_lists() {
+    check_parse(
+        super::parse_list,
+        r"Fruits::
+* apple
+* banana
+. peel it
+. eat it
+Vegetables:: Greens
+- spinach
+",
+        expect![[r#"
+            List {
+                items: [
+                    ListItem {
+                        kind: Description(
+                            [
+                                Text(
+                                    "Fruits",
+                                ),
+                            ],
+                        ),
+                        level: 1,
+                        paragraph: [],
+                        blocks: [
+                            Block {
+                                context: List(
+                                    List {
+                                        items: [
+                                            ListItem {
+                                                kind: Unordered,
+                                                level: 2,
+                                                paragraph: [
+                                                    Text(
+                                                        "apple",
+                                                    ),
+                                                ],
+                                                blocks: [],
+                                                bibliography: None,
+                                            },
+                                            ListItem {
+                                                kind: Unordered,
+                                                level: 2,
+                                                paragraph: [
+                                                    Text(
+                                                        "banana",
+                                                    ),
+                                                ],
+                                                blocks: [
+                                                    Block {
+                                                        context: List(
+                                                            List {
+                                                                items: [
+                                                                    ListItem {
+                                                                        kind: Ordered,
+                                                                        level: 3,
+                                                                        paragraph: [
+                                                                            Text(
+                                                                                "peel it",
+                                                                            ),
+                                                                        ],
+                                                                        blocks: [],
+                                                                        bibliography: None,
+                                                                    },
+                                                                    ListItem {
+                                                                        kind: Ordered,
+                                                                        level: 3,
+                                                                        paragraph: [
+                                                                            Text(
+                                                                                "eat it",
+                                                                            ),
+                                                                        ],
+                                                                        blocks: [],
+                                                                        bibliography: None,
+                                                                    },
+                                                                ],
+                                                                style: None,
+                                                                start: None,
+                                                            },
+                                                        ),
+                                                        title: None,
+                                                        attributes: {},
+                                                        id: None,
+                                                        roles: [],
+                                                        options: [],
+                                                        callouts: [],
+                                                    },
+                                                ],
+                                                bibliography: None,
+                                            },
+                                        ],
+                                        style: None,
+                                        start: None,
+                                    },
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                        ],
+                        bibliography: None,
+                    },
+                    ListItem {
+                        kind: Description(
+                            [
+                                Text(
+                                    "Vegetables",
+                                ),
+                            ],
+                        ),
+                        level: 1,
+                        paragraph: [],
+                        blocks: [
+                            Block {
+                                context: Paragraph(
+                                    "Greens",
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                            Block {
+                                context: List(
+                                    List {
+                                        items: [
+                                            ListItem {
+                                                kind: Unordered,
+                                                level: 2,
+                                                paragraph: [
+                                                    Text(
+                                                        "spinach",
+                                                    ),
+                                                ],
+                                                blocks: [],
+                                                bibliography: None,
+                                            },
+                                        ],
+                                        style: None,
+                                        start: None,
+                                    },
+                                ),
+                                title: None,
+                                attributes: {},
+                                id: None,
+                                roles: [],
+                                options: [],
+                                callouts: [],
+                            },
+                        ],
+                        bibliography: None,
+                    },
+                ],
+                style: None,
+                start: None,
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_inlines_incrementally() {
+    use nom::bytes::complete::tag;
+    use nom::combinator::not;
+    use nom::multi::many1;
+    use nom::sequence::preceded;
+
+    let input = Span::new("A *bold* line\nand _another_ one\n\nNext paragraph");
+    let (rest, first) = super::parse_inlines_line::<()>(input).unwrap();
+    assert_eq!(rest.text(), "and _another_ one\n\nNext paragraph");
+    assert_eq!(rest.location_line(), 2);
+    assert_eq!(first.len(), 3);
+
+    // composes with other combinators, here stopping at the blank line
+    let mut lines = many1(preceded(not(tag("\n")), super::parse_inlines_line::<()>));
+    let (rest, lines) = lines(input).unwrap();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(rest.text(), "\nNext paragraph");
+    assert_eq!(rest.location_offset(), input.text().find("\nNext").unwrap());
+
+    let (rest, tags) = super::parse_inlines_line::<()>(Span::new("no newline")).unwrap();
+    assert_eq!((rest.text(), tags.len()), ("", 1));
+    assert!(super::parse_inlines_line::<()>(rest).is_err());
+}
+
+#[test]
+fn parse_footnotes() {
+    check_parse(
+        super::parse_inlines,
+        "Text.footnote:[A *plain* note.] Defined footnoteref:[n1,A legacy, named note.] and \
+         referenced footnoteref:[n1] and footnote:n2[Named.] footnote:n2[]",
+        expect![[r#"
+            [
+                Text(
+                    "Text.",
+                ),
+                Footnote {
+                    id: None,
+                    content: Some(
+                        [
+                            Text(
+                                "A ",
+                            ),
+                            Format(
+                                Bold,
+                                [
+                                    Text(
+                                        "plain",
+                                    ),
+                                ],
+                            ),
+                            Text(
+                                " note.",
+                            ),
+                        ],
+                    ),
+                },
+                Text(
+                    " Defined ",
+                ),
+                Footnote {
+                    id: Some(
+                        "n1",
+                    ),
+                    content: Some(
+                        [
+                            Text(
+                                "A legacy, named note.",
+                            ),
+                        ],
+                    ),
+                },
+                Text(
+                    " and referenced ",
+                ),
+                Footnote {
+                    id: Some(
+                        "n1",
+                    ),
+                    content: None,
+                },
+                Text(
+                    " and ",
+                ),
+                Footnote {
+                    id: Some(
+                        "n2",
+                    ),
+                    content: Some(
+                        [
+                            Text(
+                                "Named.",
+                            ),
+                        ],
+                    ),
+                },
+                Text(
+                    " ",
+                ),
+                Footnote {
+                    id: Some(
+                        "n2",
+                    ),
+                    content: None,
+                },
+            ]
+        "#]],
+    );
+    // an escaped `]` doesn't close the footnote
+    check_parse(
+        super::parse_inlines,
+        r"x footnote:[a [b\] c] y footnote:[See https://x[docs\] more]",
+        expect![[r#"
+            [
+                Text(
+                    "x ",
+                ),
+                Footnote {
+                    id: None,
+                    content: Some(
+                        [
+                            Text(
+                                "a [b\\] c",
+                            ),
+                        ],
+                    ),
+                },
+                Text(
+                    " y ",
+                ),
+                Footnote {
+                    id: None,
+                    content: Some(
+                        [
+                            Text(
+                                "See https://x[docs\\] more",
+                            ),
+                        ],
+                    ),
+                },
+            ]
+        "#]],
+    );
+}
+
+#[test]
+fn parse_ui_macros() {
+    check_parse(
+        super::parse_inlines,
+        r#"Press btn:[ OK ] or use menu:View[Zoom > "Fit > Width" >Reset] and menu:File[]."#,
+        expect![[r#"
+            [
+                Text(
+                    "Press ",
+                ),
+                Button(
+                    "OK",
+                ),
+                Text(
+                    " or use ",
+                ),
+                Menu(
+                    [
+                        "View",
+                        "Zoom",
+                        "Fit > Width",
+                        "Reset",
+                    ],
+                ),
+                Text(
+                    " and ",
+                ),
+                Menu(
+                    [
+                        "File",
+                    ],
+                ),
+                Text(
+                    ".",
+                ),
+            ]
+        "#]],
+    );
+}
+
+#[test]
+fn parse_index_terms() {
+    check_parse(
+        super::parse_inlines,
+        "The ((foo)) bar(((a, b)))indexterm:[x,y,z] and indexterm2:[baz]",
+        expect![[r#"
+            [
+                Text(
+                    "The ",
+                ),
+                IndexTerm {
+                    primary: "foo",
+                    secondary: None,
+                    tertiary: None,
+                    visible: true,
+                },
+                Text(
+                    " bar",
+                ),
+                IndexTerm {
+                    primary: "a",
+                    secondary: Some(
+                        "b",
+                    ),
+                    tertiary: None,
+                    visible: false,
+                },
+                IndexTerm {
+                    primary: "x",
+                    secondary: Some(
+                        "y",
+                    ),
+                    tertiary: Some(
+                        "z",
+                    ),
+                    visible: false,
+                },
+                Text(
+                    " and ",
+                ),
+                IndexTerm {
+                    primary: "baz",
+                    secondary: None,
+                    tertiary: None,
+                    visible: true,
+                },
+            ]
+        "#]],
+    );
+}
+
+#[test]
+fn parse_bibliography() {
+    check_parse(
+        super::parse_attributed_block,
+        r#"[bibliography]
+== References
+
+* [[[pp]]] Andy Hunt & Dave Thomas. _The Pragmatic Programmer_.
+* [[[gof,GoF]]] Erich Gamma et al. Design Patterns.
+"#,
+        expect![[r#"
+            Block {
+                context: SectionTitle(
+                    SectionTitle {
+                        level: 1,
+                        content: "References",
+                        style: Some(
+                            Bibliography,
+                        ),
+                    },
+                    [
+                        Block {
+                            context: List(
+                                List {
+                                    items: [
+                                        ListItem {
+                                            kind: Unordered,
+                                            level: 1,
+                                            paragraph: [
+                                                Text(
+                                                    "Andy Hunt & Dave Thomas. ",
+                                                ),
+                                                Format(
+                                                    Italic,
+                                                    [
+                                                        Text(
+                                                            "The Pragmatic Programmer",
+                                                        ),
+                                                    ],
+                                                ),
+                                                Text(
+                                                    ".",
+                                                ),
+                                            ],
+                                            blocks: [],
+                                            bibliography: Some(
+                                                BibliographyEntry {
+                                                    id: "pp",
+                                                    reftext: None,
+                                                },
+                                            ),
+                                        },
+                                        ListItem {
+                                            kind: Unordered,
+                                            level: 1,
+                                            paragraph: [
+                                                Text(
+                                                    "Erich Gamma et al. Design Patterns.",
+                                                ),
+                                            ],
+                                            blocks: [],
+                                            bibliography: Some(
+                                                BibliographyEntry {
+                                                    id: "gof",
+                                                    reftext: Some(
+                                                        "GoF",
+                                                    ),
+                                                },
+                                            ),
+                                        },
+                                    ],
+                                    style: None,
+                                    start: None,
+                                },
+                            ),
+                            title: None,
+                            attributes: {},
+                            id: None,
+                            roles: [],
+                            options: [],
+                            callouts: [],
+                        },
+                    ],
+                ),
+                title: None,
+                attributes: {
+                    "bibliography": None,
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+    check_parse(
+        super::parse_attributed_block,
+        "[bibliography]\n== References\n\n[[[pp]]] The Pragmatic Programmer.\n",
+        expect![[r#"
+            Block {
+                context: SectionTitle(
+                    SectionTitle {
+                        level: 1,
+                        content: "References",
+                        style: Some(
+                            Bibliography,
+                        ),
+                    },
+                    [
+                        Block {
+                            context: BibliographyEntry {
+                                entry: BibliographyEntry {
+                                    id: "pp",
+                                    reftext: None,
+                                },
+                                content: "The Pragmatic Programmer.",
+                            },
+                            title: None,
+                            attributes: {},
+                            id: None,
+                            roles: [],
+                            options: [],
+                            callouts: [],
+                        },
+                    ],
+                ),
+                title: None,
+                attributes: {
+                    "bibliography": None,
+                },
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_nested_delimited_blocks() {
+    check_parse(
+        super::parse_attributed_block,
+        r#"=====
+Outer example.
+
+====
+Inner example.
+====
+
+----
+fn main() {}
+====
+----
+=====
+"#,
+        expect![[r#"
+            Block {
+                context: Example(
+                    [
+                        Block {
+                            context: Paragraph(
+                                "Outer example.",
+                            ),
+                            title: None,
+                            attributes: {},
+                            id: None,
+                            roles: [],
+                            options: [],
+                            callouts: [],
+                        },
+                        Block {
+                            context: Example(
+                                [
+                                    Block {
+                                        context: Paragraph(
+                                            "Inner example.",
+                                        ),
+                                        title: None,
+                                        attributes: {},
+                                        id: None,
+                                        roles: [],
+                                        options: [],
+                                        callouts: [],
+                                    },
+                                ],
+                            ),
+                            title: None,
+                            attributes: {},
+                            id: None,
+                            roles: [],
+                            options: [],
+                            callouts: [],
+                        },
+                        Block {
+                            context: Listing(
+                                "fn main() {}\n====\n",
+                            ),
+                            title: None,
+                            attributes: {},
+                            id: None,
+                            roles: [],
+                            options: [],
+                            callouts: [],
+                        },
+                    ],
+                ),
+                title: None,
+                attributes: {},
+                id: None,
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_section_title_trailing_anchor() {
+    check_parse(
+        super::parse_attributed_block,
+        "== Introduction [[intro]]\n\n=== Using [[ in Bash [#bash-tests]\n\n=== Arrays [[0]]\n",
+        expect![[r#"
+            Block {
+                context: SectionTitle(
+                    SectionTitle {
+                        level: 1,
+                        content: "Introduction",
+                        style: None,
+                    },
+                    [
+                        Block {
+                            context: SectionTitle(
+                                SectionTitle {
+                                    level: 2,
+                                    content: "Using [[ in Bash",
+                                    style: None,
+                                },
+                                [],
+                            ),
+                            title: None,
+                            attributes: {},
+                            id: Some(
+                                "bash-tests",
+                            ),
+                            roles: [],
+                            options: [],
+                            callouts: [],
+                        },
+                        Block {
+                            context: SectionTitle(
+                                SectionTitle {
+                                    level: 2,
+                                    content: "Arrays [[0]]",
+                                    style: None,
+                                },
+                                [],
+                            ),
+                            title: None,
+                            attributes: {},
+                            id: None,
+                            roles: [],
+                            options: [],
+                            callouts: [],
+                        },
+                    ],
+                ),
+                title: None,
+                attributes: {},
+                id: Some(
+                    "intro",
+                ),
+                roles: [],
+                options: [],
+                callouts: [],
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn parse_image_attributes() {
+    let image = |input| {
+        let (_, image) = super::parse_block_macro::<()>(Span::new(input)).unwrap();
+        crate::ast::ImageAttributes::from(&image)
+    };
+    expect![[r#"
+        ImageAttributes {
+            alt: Some(
+                "Alt Text",
+            ),
+            width: Some(
+                "200",
+            ),
+            height: Some(
+                "100",
+            ),
+            link: None,
+            float: None,
+            align: None,
+            title: None,
+        }
+    "#]]
+    .assert_debug_eq(&image("image::x.png[Alt Text,200,100]"));
+    expect![[r#"
+        ImageAttributes {
+            alt: Some(
+                "Alt, Text",
+            ),
+            width: Some(
+                "240",
+            ),
+            height: None,
+            link: Some(
+                "https://example.org",
+            ),
+            float: None,
+            align: None,
+            title: None,
+        }
+    "#]]
+    .assert_debug_eq(&image("image::x.png[\"Alt, Text\",width=240,link=https://example.org]"));
+    // positionals are counted by their position among all entries, equal and empty ones included
+    let dimensions = |input| {
+        let image = image(input);
+        (image.alt, image.width, image.height)
+    };
+    expect![[r#"
+        (
+            Some(
+                "Logo",
+            ),
+            Some(
+                "200",
+            ),
+            Some(
+                "200",
+            ),
+        )
+    "#]]
+    .assert_debug_eq(&dimensions("image::x.png[Logo,200,200]"));
+    expect![[r#"
+        (
+            None,
+            Some(
+                "200",
+            ),
+            Some(
+                "100",
+            ),
+        )
+    "#]]
+    .assert_debug_eq(&dimensions("image::x.png[,200,100]"));
+    expect![[r#"
+        (
+            Some(
+                "Alt",
+            ),
+            Some(
+                "300",
+            ),
+            Some(
+                "100",
+            ),
+        )
+    "#]]
+    .assert_debug_eq(&dimensions("image::x.png[Alt,width=300,100]"));
+}
+
+#[test]
+fn parse_breaks() {
+    let contexts = |src| {
+        super::parse_blocks::<VerboseError<Span>>(Span::new(src))
+            .map(|(rest, blocks)| (rest, blocks.into_iter().map(|b| b.context).collect::<Vec<_>>()))
+    };
+    expect![[r#"
+        Ok(
+            (
+                "",
+                [
+                    ThematicBreak,
+                    Paragraph(
+                        "paragraph",
+                    ),
+                ],
+            ),
+        )
+    "#]]
+    .assert_debug_eq(&contexts("'''\nparagraph\n"));
+    expect![[r#"
+        Ok(
+            (
+                "",
+                [
+                    ThematicBreak,
+                    PageBreak,
+                    ThematicBreak,
+                ],
+            ),
+        )
+    "#]]
+    .assert_debug_eq(&contexts("'''\n\n>>>\n'''\n"));
+    // trailing whitespace is fine, other trailing text makes the line a paragraph
+    expect![[r#"
+        Ok(
+            (
+                "",
+                [
+                    ThematicBreak,
+                    Paragraph(
+                        "paragraph",
+                    ),
+                    Paragraph(
+                        "'''text",
+                    ),
+                ],
+            ),
+        )
+    "#]]
+    .assert_debug_eq(&contexts("''' \nparagraph\n\n'''text\n"));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "escapes the consumed input")]
+fn bounded_rejects_fabricated_spans() {
+    let fabricated = |i: Span<'static>| -> IResult<_, _, ()> { Ok((i, Span::new("fabricated"))) };
+    let _ = super::nom_ext::bounded(fabricated)(Span::new("input"));
+}
@@ -0,0 +1,136 @@
+//! The extension point for output backends.
+
+use crate::ast::*;
+use crate::parser::parse_block_text;
+use crate::Span;
+
+/// A backend turning a [`Document`] into text.
+///
+/// Every hook appends its output to `out`. The hooks of nodes containing other blocks walk their
+/// children by default, while the hooks of leaf nodes render nothing, so a backend only has to
+/// implement the hooks of the nodes it cares about.
+pub trait Renderer {
+    /// Renders a whole document.
+    fn render(&self, doc: &Document<'_>) -> String {
+        let mut out = String::new();
+        if let Some(header) = &doc.header {
+            self.render_header(&mut out, header);
+        }
+        self.render_blocks(&mut out, &doc.content);
+        out
+    }
+
+    fn render_header(&self, _out: &mut String, _header: &DocumentHeader<'_>) {}
+
+    fn render_blocks(&self, out: &mut String, blocks: &[Block<'_>]) {
+        for block in blocks {
+            self.render_block(out, block);
+        }
+    }
+
+    /// Dispatches to the hook of the block's context.
+    fn render_block(&self, out: &mut String, block: &Block<'_>) {
+        match &block.context {
+            Context::SectionTitle(title, blocks) => self.render_section(out, block, title, blocks),
+            Context::Paragraph(_) => {
+                let tags = parse_block_text(block).unwrap_or_default();
+                self.render_paragraph(out, block, &tags);
+            }
+            Context::Admonition { blocks, .. }
+            | Context::Example(blocks)
+            | Context::Sidebar(blocks)
+            | Context::Open(blocks)
+            | Context::Quote(blocks) => self.render_compound(out, block, blocks),
+            Context::Listing(content)
+            | Context::Literal(content)
+            | Context::Verse(Verse { content, .. })
+            | Context::Passthrough(content)
+            | Context::Stem { content, .. } => self.render_verbatim(out, block, *content),
+            Context::List(list) => self.render_list(out, block, list),
+            Context::Table(table) => self.render_table(out, block, table),
+            _ => self.render_other(out, block),
+        }
+    }
+
+    fn render_section(
+        &self,
+        out: &mut String,
+        _block: &Block<'_>,
+        _title: &SectionTitle<'_>,
+        blocks: &[Block<'_>],
+    ) {
+        self.render_blocks(out, blocks);
+    }
+
+    fn render_paragraph(&self, out: &mut String, _block: &Block<'_>, tags: &[Tag<'_>]) {
+        self.render_inlines(out, tags);
+    }
+
+    /// Renders a block containing other blocks, like an example, sidebar or admonition block.
+    fn render_compound(&self, out: &mut String, _block: &Block<'_>, blocks: &[Block<'_>]) {
+        self.render_blocks(out, blocks);
+    }
+
+    /// Renders a block whose content isn't parsed any further, like a listing or stem block.
+    fn render_verbatim(&self, _out: &mut String, _block: &Block<'_>, _content: Span<'_>) {}
+
+    fn render_list(&self, out: &mut String, _block: &Block<'_>, list: &List<'_>) {
+        for item in &list.items {
+            self.render_inlines(out, &item.paragraph);
+            self.render_blocks(out, &item.blocks);
+        }
+    }
+
+    fn render_table(&self, _out: &mut String, _block: &Block<'_>, _table: &Table<'_>) {}
+
+    fn render_inlines(&self, _out: &mut String, _tags: &[Tag<'_>]) {}
+
+    /// Renders the remaining blocks, like block macros, breaks and comments.
+    fn render_other(&self, _out: &mut String, _block: &Block<'_>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_document_lossy;
+
+    /// Renders the titles of the sections, indented by their level.
+    struct Outline;
+
+    impl Renderer for Outline {
+        fn render_section(
+            &self,
+            out: &mut String,
+            _block: &Block<'_>,
+            title: &SectionTitle<'_>,
+            blocks: &[Block<'_>],
+        ) {
+            out.push_str(&"  ".repeat(title.level - 1));
+            out.push_str(&title.content);
+            out.push('\n');
+            self.render_blocks(out, blocks);
+        }
+    }
+
+    #[test]
+    fn test_outline_renderer() {
+        let source = r"= Document
+
+Preamble.
+
+== First
+
+Some text.
+
+=== Nested
+
+====
+An example.
+====
+
+== Second
+";
+        let (doc, _) = parse_document_lossy(source);
+        assert_eq!(Outline.render(&doc), "First\n  Nested\nSecond\n");
+    }
+}
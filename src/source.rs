@@ -1,16 +1,13 @@
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_till, take_till1};
-use nom::combinator::{map, recognize};
-use nom::sequence::{delimited, pair, preceded};
-use nom::IResult;
+use nom::bytes::complete::{tag, take_till, take_till1, take_while, take_while1};
+use nom::combinator::{eof, map, recognize, rest, verify};
+use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::{IResult, Offset};
 
-use crate::attributes::AttributeMap;
+use std::borrow::Cow;
+use std::ops::Range;
 
-#[derive(Clone, Copy, Debug, Default)]
-pub struct TextRange {
-    start: usize,
-    end: usize,
-}
+use crate::attributes::AttributeMap;
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct LineIndex(usize);
@@ -21,57 +18,300 @@ pub struct FileId(usize);
 #[derive(Debug)]
 struct CondDirective {
     targets: String,
-    /// Whether this directive caused us to start skipping
+    /// Whether the condition of this directive is unmet, skipping its content
     skipping: bool,
+    /// The file this directive was opened in
+    file: FileId,
+}
+
+const BOM: char = '\u{feff}';
+
+/// The length of the byte-order mark `source` starts with, if any. It is skipped rather than
+/// removed, so that offsets into the source stay intact.
+fn bom_len(source: &str) -> usize {
+    if source.starts_with(BOM) {
+        BOM.len_utf8()
+    } else {
+        0
+    }
 }
 
 #[derive(Debug)]
 struct Include {
+    file: FileId,
+    /// The include target, `None` for the root document
+    target: Option<String>,
     source: String,
     processed: usize,
+    /// The level offset to restore once this include has been processed
+    restore_leveloffset: Option<isize>,
+    /// Whether the lines of this include are passed through without processing directives,
+    /// as is the case for non-AsciiDoc files
+    verbatim: bool,
+    /// The tagged regions selected by the `tag` or `tags` attribute, if any
+    tags: Option<Vec<String>>,
+    /// The selected regions that are currently open
+    open_tags: Vec<String>,
 }
 
 impl Include {
-    fn next_line(&mut self) -> Option<&str> {
-        let (_, line) = <IResult<_, _, ()>>::ok(recognize(take_till(|c| c == '\n'))(
-            self.source.get(self.processed..)?,
-        ))?;
-        let l = line.len();
-        self.processed += l + 1;
-        if let Some(b'\r') = line.as_bytes().last() {
-            Some(&line[0..l - 1])
-        } else {
-            Some(line)
+    /// Returns the next line along with its offset in the source, skipping the lines outside of
+    /// the selected tagged regions.
+    fn next_line(&mut self) -> Option<(usize, &str)> {
+        loop {
+            let start = self.processed;
+            let (_, line) = <IResult<_, _, ()>>::ok(recognize(take_till(|c| c == '\n'))(
+                self.source.get(start..)?,
+            ))?;
+            self.processed += line.len() + 1;
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let tags = match &self.tags {
+                Some(tags) => tags,
+                None => return Some((start, line)),
+            };
+            match Self::tag_marker(line) {
+                Some((true, name)) if tags.iter().any(|tag| tag == name) => {
+                    self.open_tags.push(name.to_owned())
+                }
+                Some((true, _)) => (),
+                Some((false, name)) => {
+                    if let Some(idx) = self.open_tags.iter().rposition(|open| open == name) {
+                        self.open_tags.remove(idx);
+                    }
+                }
+                None if !self.open_tags.is_empty() => return Some((start, line)),
+                None => (),
+            }
         }
     }
+
+    /// Finds a `tag::name[]` or `end::name[]` marker on `line`, returning whether it starts a
+    /// region and the region's name. The marker may appear anywhere on the line, as it is
+    /// usually written in a comment of the included file's language, e.g. `// tag::name[]` or
+    /// `<!-- tag::name[] -->`.
+    fn tag_marker(line: &str) -> Option<(bool, &str)> {
+        let (idx, start) = line
+            .match_indices("::")
+            .filter_map(|(idx, _)| match &line[..idx] {
+                before if before.ends_with("tag") => Some((idx - 3, true)),
+                before if before.ends_with("end") => Some((idx - 3, false)),
+                _ => None,
+            })
+            .find(|&(idx, _)| !line[..idx].ends_with(|c: char| c.is_alphanumeric() || c == '_'))?;
+        let rest = &line[idx + "tag::".len()..];
+        let (name, rest) = rest.split_once("[]")?;
+        let is_name = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+        if name.is_empty() || !name.chars().all(is_name) {
+            return None;
+        }
+        if !(rest.is_empty() || rest.starts_with(char::is_whitespace)) {
+            return None;
+        }
+        Some((start, name))
+    }
+}
+
+/// A line of the amalgamated source and the range of the original file it was taken from
+#[derive(Debug, PartialEq, Eq)]
+struct LineMapping {
+    start: usize,
+    len: usize,
+    file: FileId,
+    original: Range<usize>,
+}
+
+/// Maps the lines of the amalgamated source back to the files they originate from.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SourceMap {
+    lines: Vec<LineMapping>,
+}
+
+impl SourceMap {
+    /// Appends `line` to `amalgamated`, recording that it was taken from `original` in `file`.
+    fn push_line(
+        &mut self,
+        amalgamated: &mut String,
+        line: &str,
+        (file, original): (FileId, Range<usize>),
+    ) {
+        let start = amalgamated.len();
+        self.lines.push(LineMapping { start, len: line.len(), file, original });
+        amalgamated.reserve(line.len() + 1);
+        amalgamated.push_str(line);
+        amalgamated.push('\n');
+    }
+
+    fn original_range(&self, range: Range<usize>) -> Option<(FileId, Range<usize>)> {
+        if range.start > range.end {
+            return None;
+        }
+        let (start_file, start) = self.original_offset(range.start, false)?;
+        let (end_file, end) = self.original_offset(range.end, range.end > range.start)?;
+        if start_file != end_file {
+            return None;
+        }
+        Some((start_file, start..end))
+    }
+
+    /// Maps an offset into the amalgamated source to an offset into the original file. If
+    /// `is_end` is set, an offset at the start of a line is mapped to the end of the preceding
+    /// one.
+    fn original_offset(&self, offset: usize, is_end: bool) -> Option<(FileId, usize)> {
+        let idx = match is_end {
+            true => self.lines.partition_point(|line| line.start < offset),
+            false => self.lines.partition_point(|line| line.start <= offset),
+        };
+        let line = &self.lines[idx.checked_sub(1)?];
+        let within = offset - line.start;
+        let original = match within.checked_sub(line.len) {
+            Some(0) | None => {
+                // lines are only ever rewritten at their start, like the markers of section titles
+                let shift = line.original.len() as isize - line.len as isize;
+                line.original.start + (within as isize + shift).max(0) as usize
+            }
+            // the newline following the line
+            Some(1) => line.original.end + 1,
+            Some(_) => return None,
+        };
+        Some((line.file, original))
+    }
+}
+
+/// The safe mode restricting which files may be included, mirroring AsciiDoctor's safe modes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SafeMode {
+    /// Any include target is passed to the include callback
+    #[default]
+    Unsafe,
+    /// Like `Unsafe` as far as includes are concerned
+    Safe,
+    /// Include targets that are absolute or contain `..` are rejected, as are URIs unless the
+    /// `allow-uri-read` attribute is set
+    Server,
+    /// Like `Server` as far as includes are concerned
+    Secure,
 }
 
 pub struct DocumentSource {
     amalgamated: String,
+    source_map: SourceMap,
+    /// The include targets of the files making up the document, indexed by their [`FileId`]
+    files: Vec<Option<String>>,
+    warnings: Vec<PreprocessWarning>,
 }
 
 impl DocumentSource {
     pub fn new<S, E, CB>(source: S, include_cb: CB) -> Result<Self, PreprocessError<E>>
+    where
+        S: Into<String>,
+        CB: FnMut(&AttributeMap, &str) -> Result<String, E>,
+    {
+        Self::with_safe_mode(source, SafeMode::default(), include_cb)
+    }
+
+    /// Like [`new`](Self::new), but decodes the source from `bytes` in the given `encoding`
+    /// first. Included files still have to be decoded by `include_cb`.
+    #[cfg(feature = "encoding")]
+    pub fn from_bytes<E, CB>(
+        bytes: &[u8],
+        encoding: crate::encoding::Encoding,
+        include_cb: CB,
+    ) -> Result<Self, PreprocessError<E>>
+    where
+        CB: FnMut(&AttributeMap, &str) -> Result<String, E>,
+    {
+        Self::new(encoding.decode(bytes), include_cb)
+    }
+
+    /// Like [`new`](Self::new), but restricts the include targets passed to `include_cb`
+    /// according to `safe_mode`.
+    pub fn with_safe_mode<S, E, CB>(
+        source: S,
+        safe_mode: SafeMode,
+        include_cb: CB,
+    ) -> Result<Self, PreprocessError<E>>
     where
         S: Into<String>,
         CB: FnMut(&AttributeMap, &str) -> Result<String, E>,
     {
         let mut pp = Preprocessor::new(source.into(), include_cb, Default::default());
+        pp.safe_mode = safe_mode;
         pp.amalgamate()?;
-        Ok(DocumentSource { amalgamated: pp.amalgamated })
+        Ok(DocumentSource {
+            amalgamated: pp.amalgamated,
+            source_map: pp.source_map,
+            files: pp.files,
+            warnings: pp.warnings,
+        })
+    }
+
+    /// The preprocessed source text, with all includes and conditionals resolved
+    pub fn amalgamated(&self) -> &str {
+        &self.amalgamated
+    }
+
+    /// Consumes the source, returning the preprocessed source text
+    pub fn into_string(self) -> String {
+        self.amalgamated
+    }
+
+    /// Maps a byte range of the amalgamated source, like the one of a parsed
+    /// [`Span`](crate::Span), back to the file it originates from and the range within that file.
+    /// Returns `None` if the range spans multiple files.
+    pub fn original_range(&self, range: Range<usize>) -> Option<(FileId, Range<usize>)> {
+        self.source_map.original_range(range)
+    }
+
+    /// The include target `file` was included by, `None` for the root document.
+    pub fn file_target(&self, file: FileId) -> Option<&str> {
+        self.files.get(file.0)?.as_deref()
+    }
+
+    /// The problems encountered while preprocessing that did not make it fail
+    pub fn warnings(&self) -> &[PreprocessWarning] {
+        &self.warnings
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum PreprocessError<IE> {
     MaxIncludeDepthReached,
-    IncludeError(IE),
+    /// The include of `target` is not permitted by the [`SafeMode`]
+    IncludeNotPermitted {
+        target: String,
+    },
+    /// The include callback failed to provide the content of `target`
+    IncludeError {
+        target: String,
+        source: IE,
+    },
+    /// A file ended with conditionals it opened still being open, or closed a conditional that
+    /// was opened in a different file. `file` is `None` for the root document.
+    UnbalancedConditional {
+        file: Option<String>,
+    },
+    /// An `endif` with targets that don't match any open conditional. `file` is `None` for the
+    /// root document.
+    UnmatchedEndIf {
+        targets: String,
+        file: Option<String>,
+    },
+    /// A conditional directive on the 1-based `line` that lacks its target, like `ifdef::[]`.
+    /// `file` is `None` for the root document.
+    MalformedDirective {
+        line: usize,
+        file: Option<String>,
+    },
 }
 
-impl<IE> From<IE> for PreprocessError<IE> {
-    fn from(ie: IE) -> Self {
-        PreprocessError::IncludeError(ie)
-    }
+/// A problem with the source that doesn't stop it from being preprocessed, see
+/// [`DocumentSource::warnings`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PreprocessWarning {
+    /// An `ifeval` on the 1-based `line` whose expression could not be evaluated, like
+    /// `ifeval::[{sectnumlevels} > 2]`. Its content is kept as if the expression held. `file` is
+    /// `None` for the root document.
+    UnevaluableExpression { expression: String, line: usize, file: Option<String> },
 }
 
 struct Preprocessor<E, CB>
@@ -85,11 +325,22 @@ where
     include_cb: CB,
     max_include_depth: usize,
     attribute_map: AttributeMap,
+    /// The amount of levels section titles are currently shifted by
+    leveloffset: isize,
+    /// The include targets of the files processed so far, indexed by their [`FileId`]
+    files: Vec<Option<String>>,
+    source_map: SourceMap,
+    safe_mode: SafeMode,
+    warnings: Vec<PreprocessWarning>,
+    /// Whether lines other than conditionals are dropped without parsing them while skipping.
+    /// Tests turn this off to check that it doesn't change the result.
+    #[cfg(test)]
+    fast_skip: bool,
 }
 
-impl<'cb, E, CB> Preprocessor<E, CB>
+impl<E, CB> Preprocessor<E, CB>
 where
-    CB: for<'a> FnMut(&AttributeMap, &'a str) -> Result<String, E> + 'cb,
+    CB: for<'a> FnMut(&AttributeMap, &'a str) -> Result<String, E>,
 {
     pub fn new(source: impl Into<String>, include_cb: CB, attributes: AttributeMap) -> Self {
         let source = source.into();
@@ -97,117 +348,373 @@ where
             skipping: false,
             amalgamated: String::with_capacity(source.len()),
             conditional_stack: vec![],
-            include_stack: vec![Include { source, processed: 0 }],
+            include_stack: vec![Include {
+                file: FileId(0),
+                target: None,
+                processed: bom_len(&source),
+                source,
+                restore_leveloffset: None,
+                verbatim: false,
+                tags: None,
+                open_tags: Vec::new(),
+            }],
             include_cb,
             max_include_depth: 64,
             attribute_map: attributes,
+            leveloffset: 0,
+            files: vec![None],
+            source_map: SourceMap::default(),
+            safe_mode: SafeMode::default(),
+            warnings: Vec::new(),
+            #[cfg(test)]
+            fast_skip: true,
         }
     }
 
     pub fn amalgamate(&mut self) -> Result<(), PreprocessError<E>> {
         loop {
             let n_includes = self.include_stack.len();
-            let line = match self.include_stack.last_mut() {
-                Some(include) => match include.next_line() {
-                    Some(line) => line,
-                    None => {
-                        self.include_stack.pop();
+            let fast_skip = self.fast_skip();
+            let (file, line_start, line, verbatim) = match self.include_stack.last_mut() {
+                Some(include) => match (include.file, include.verbatim, include.next_line()) {
+                    (file, verbatim, Some((start, line))) => (file, start, line, verbatim),
+                    (.., None) => {
+                        if let Include { restore_leveloffset: Some(leveloffset), .. } =
+                            self.pop_include()?
+                        {
+                            self.leveloffset = leveloffset;
+                        }
                         continue;
                     }
                 },
                 None => break,
             };
+            // the origin of a part of the current line
+            let origin = |text: &str| {
+                let start = line_start + line.offset(text);
+                (file, start..start + text.len())
+            };
 
-            if let Some(doc_attrib) = Self::parse_doc_attrib(line) {
+            if verbatim {
+                if !self.skipping {
+                    self.source_map.push_line(&mut self.amalgamated, line, origin(line));
+                }
+            } else if self.skipping && fast_skip && !Self::is_conditional_directive(line) {
+                // while skipping, only conditionals need to be processed to track their nesting
+                continue;
             } else if let Some(directive) = Self::parse_pp_directive(line) {
                 match directive {
-                    PreprocessorDirective::EndIf { targets: _ } => {
-                        // FIXME: check that targets match
-                        if let Some(directive) = self.conditional_stack.pop() {
-                            if directive.skipping {
-                                self.skipping = false;
+                    PreprocessorDirective::EndIf { targets } => {
+                        // a targeted endif closes the innermost conditional with the same targets
+                        let idx = if targets.is_empty() {
+                            self.conditional_stack.len().checked_sub(1)
+                        } else {
+                            let idx = self
+                                .conditional_stack
+                                .iter()
+                                .rposition(|directive| directive.targets == targets);
+                            if idx.is_none() {
+                                return Err(PreprocessError::UnmatchedEndIf {
+                                    targets: targets.to_owned(),
+                                    file: self.current_target(),
+                                });
+                            }
+                            idx
+                        };
+                        if let Some(idx) = idx {
+                            if self.conditional_stack[idx].file != self.current_file() {
+                                let file = self.current_target();
+                                return Err(PreprocessError::UnbalancedConditional { file });
                             }
+                            self.conditional_stack.remove(idx);
+                            self.skipping =
+                                self.conditional_stack.iter().any(|directive| directive.skipping);
                         }
                     }
-                    PreprocessorDirective::Include { target, attributes: _ } if !self.skipping => {
+                    PreprocessorDirective::Malformed => {
+                        let line = self.current_line(line_start);
+                        let file = self.current_target();
+                        return Err(PreprocessError::MalformedDirective { line, file });
+                    }
+                    PreprocessorDirective::Include { target, attributes } if !self.skipping => {
                         if n_includes >= self.max_include_depth {
                             return Err(PreprocessError::MaxIncludeDepthReached);
                         }
-                        let source = (self.include_cb)(&self.attribute_map, target)?;
+                        let mut restore_leveloffset = None;
+                        if let Some(offset) = Self::include_attribute(attributes, "leveloffset") {
+                            restore_leveloffset = Some(self.leveloffset);
+                            self.leveloffset = Self::resolve_leveloffset(self.leveloffset, offset);
+                        }
+                        let verbatim = Self::is_verbatim_include(target, attributes);
+                        let target = target.to_owned();
+                        if !Self::is_include_permitted(self.safe_mode, &self.attribute_map, &target)
+                        {
+                            return Err(PreprocessError::IncludeNotPermitted { target });
+                        }
+                        let source = match (self.include_cb)(&self.attribute_map, &target) {
+                            Ok(source) => source,
+                            Err(source) => {
+                                return Err(PreprocessError::IncludeError { target, source })
+                            }
+                        };
+                        let tags = Self::include_attribute(attributes, "tags")
+                            .or_else(|| Self::include_attribute(attributes, "tag"))
+                            .map(|tags| tags.split(';').map(|tag| tag.trim().to_owned()).collect());
                         if matches!(
                             self.include_stack.last(),
-                                Some(&Include { ref source, processed }) if processed >= source.len()
+                                Some(&Include { ref source, processed, .. }) if processed >= source.len()
                         ) {
-                            self.include_stack.pop();
+                            // the include we are replacing has to restore the offset it was
+                            // included with instead
+                            let include = self.pop_include()?;
+                            restore_leveloffset =
+                                include.restore_leveloffset.or(restore_leveloffset);
                         }
-                        self.include_stack.push(Include { processed: 0, source });
+                        let file = FileId(self.files.len());
+                        self.files.push(Some(target.clone()));
+                        self.include_stack.push(Include {
+                            file,
+                            target: Some(target),
+                            processed: bom_len(&source),
+                            source,
+                            restore_leveloffset,
+                            verbatim,
+                            tags,
+                            open_tags: Vec::new(),
+                        });
                     }
                     // would be nice to unify the following arm pairs
-                    PreprocessorDirective::IfDef { targets, inline: Some(line) } => {
+                    PreprocessorDirective::IfDef { targets, inline: Some(line) }
                         if !self.skipping
-                            && Self::check_targets_active(targets, &self.attribute_map)
-                        {
-                            Self::push_line(&mut self.amalgamated, line);
-                        }
+                            && evaluate_condition(
+                                &Condition::Defined(targets),
+                                &self.attribute_map,
+                            ) =>
+                    {
+                        let origin = origin(line);
+                        let line = Self::offset_section_title(line, self.leveloffset);
+                        self.source_map.push_line(&mut self.amalgamated, &line, origin);
                     }
                     PreprocessorDirective::IfDef { targets, inline: None } => {
-                        let skipping = !Self::check_targets_active(targets, &self.attribute_map);
+                        let skipping =
+                            !evaluate_condition(&Condition::Defined(targets), &self.attribute_map);
                         self.conditional_stack.push(CondDirective {
                             targets: targets.to_owned(),
-                            skipping: !self.skipping & skipping,
+                            skipping,
+                            file: self.current_file(),
                         });
                         self.skipping |= skipping;
                     }
-                    PreprocessorDirective::IfNotDef { targets, inline: Some(line) } => {
+                    PreprocessorDirective::IfNotDef { targets, inline: Some(line) }
                         if !self.skipping
-                            && !Self::check_targets_active(targets, &self.attribute_map)
-                        {
-                            Self::push_line(&mut self.amalgamated, line);
-                        }
+                            && !evaluate_condition(
+                                &Condition::Defined(targets),
+                                &self.attribute_map,
+                            ) =>
+                    {
+                        let origin = origin(line);
+                        let line = Self::offset_section_title(line, self.leveloffset);
+                        self.source_map.push_line(&mut self.amalgamated, &line, origin);
                     }
                     PreprocessorDirective::IfNotDef { targets, inline: None } => {
-                        let skipping = Self::check_targets_active(targets, &self.attribute_map);
+                        let skipping =
+                            evaluate_condition(&Condition::Defined(targets), &self.attribute_map);
                         self.conditional_stack.push(CondDirective {
                             targets: targets.to_owned(),
-                            skipping: !self.skipping & skipping,
+                            skipping,
+                            file: self.current_file(),
                         });
                         self.skipping |= skipping;
                     }
-                    // PreprocessorDirective::IfEval { attribute } => {} FIXME implement
+                    PreprocessorDirective::IfEval { expression } => {
+                        // expressions that aren't understood keep their content, as silently
+                        // dropping it would hide the problem
+                        let holds = match Condition::parse_expression(expression) {
+                            Some((condition, negated)) => {
+                                evaluate_condition(&condition, &self.attribute_map) != negated
+                            }
+                            None => {
+                                if !self.skipping {
+                                    self.warnings.push(PreprocessWarning::UnevaluableExpression {
+                                        expression: expression.to_owned(),
+                                        line: self.current_line(line_start),
+                                        file: self.current_target(),
+                                    });
+                                }
+                                true
+                            }
+                        };
+                        self.conditional_stack.push(CondDirective {
+                            targets: String::new(),
+                            skipping: !holds,
+                            file: self.current_file(),
+                        });
+                        self.skipping |= !holds;
+                    }
                     _ => {}
                 }
             } else if !self.skipping {
-                Self::push_line(&mut self.amalgamated, line);
+                if let Some((name, value)) = Self::parse_doc_attrib(line) {
+                    match value {
+                        Some(value) => {
+                            if name == "leveloffset" {
+                                self.leveloffset =
+                                    Self::resolve_leveloffset(self.leveloffset, value);
+                            }
+                            self.attribute_map.insert(name, value);
+                        }
+                        None => {
+                            if name == "leveloffset" {
+                                self.leveloffset = 0;
+                            }
+                            self.attribute_map.unset(name);
+                        }
+                    }
+                }
+                let origin = origin(line);
+                let line = Self::offset_section_title(line, self.leveloffset);
+                self.source_map.push_line(&mut self.amalgamated, &line, origin);
             }
         }
         self.amalgamated.pop();
         Ok(())
     }
 
-    #[inline]
-    fn push_line(amalgamated: &mut String, line: &str) {
-        amalgamated.reserve(line.len() + 1);
-        amalgamated.push_str(line);
-        amalgamated.push('\n');
+    /// Pops the innermost include, making sure it closed all the conditionals it opened.
+    fn pop_include(&mut self) -> Result<Include, PreprocessError<E>> {
+        let include = self.include_stack.pop().expect("include stack is empty");
+        match self.conditional_stack.last() {
+            Some(directive) if directive.file == include.file => {
+                Err(PreprocessError::UnbalancedConditional { file: include.target })
+            }
+            _ => Ok(include),
+        }
     }
 
-    fn parse_doc_attrib(line: &str) -> Option<(Box<str>, Option<Box<str>>)> {
-        // FIXME
-        None
+    /// The 1-based number of the line starting at `line_start` in the current file
+    fn current_line(&self, line_start: usize) -> usize {
+        self.include_stack
+            .last()
+            .map_or(0, |include| include.source[..line_start].matches('\n').count() + 1)
     }
 
-    fn check_targets_active(targets: &str, attributes: &AttributeMap) -> bool {
-        if let Some(&c) = targets.as_bytes().iter().find(|&&c| c == b'+' || c == b',') {
-            (if c == b'+' { std::str::Split::all } else { std::str::Split::any })(
-                &mut targets.split(c as char),
-                |target| attributes.contains(target),
-            )
-        } else {
-            attributes.contains(targets)
+    fn current_file(&self) -> FileId {
+        self.include_stack.last().map_or(FileId(0), |include| include.file)
+    }
+
+    fn current_target(&self) -> Option<String> {
+        self.include_stack.last().and_then(|include| include.target.clone())
+    }
+
+    /// Parses an attribute entry like `:name: value`, returning `None` as the value if the
+    /// entry unsets the attribute.
+    fn parse_doc_attrib(line: &str) -> Option<(&str, Option<&str>)> {
+        let name = take_while1(|c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '!'));
+        let value = verify(rest, |value: &str| value.is_empty() || value.starts_with(' '));
+        let (_, (name, value)) =
+            <IResult<_, _, ()>>::ok(pair(delimited(tag(":"), name, tag(":")), value)(line))?;
+        match name.strip_prefix('!').or_else(|| name.strip_suffix('!')) {
+            Some(name) => Some((name, None)),
+            None => Some((name, Some(value.trim()))),
         }
     }
 
-    #[allow(clippy::clippy::toplevel_ref_arg)]
+    /// Looks up the value of an attribute in an include directive's attribute list.
+    fn include_attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+        attributes.split(',').find_map(|attribute| {
+            let (key, value) = attribute.split_once('=')?;
+            if key.trim() == name {
+                Some(value.trim().trim_matches('"'))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Whether `safe_mode` permits including `target`. In server and secure mode, paths may
+    /// neither be absolute nor escape their directory via `..`, and URIs may only be included if
+    /// the `allow-uri-read` attribute is set.
+    fn is_include_permitted(safe_mode: SafeMode, attributes: &AttributeMap, target: &str) -> bool {
+        if safe_mode < SafeMode::Server {
+            return true;
+        }
+        if Self::is_uri(target) {
+            return attributes.contains("allow-uri-read");
+        }
+        let is_absolute = target.starts_with(&['/', '\\'][..])
+            || matches!(target.as_bytes(), [drive, b':', ..] if drive.is_ascii_alphabetic());
+        !is_absolute && !target.split(&['/', '\\'][..]).any(|component| component == "..")
+    }
+
+    /// Whether `target` is a URI, like `https://example.org/snippet.adoc`, rather than a path.
+    fn is_uri(target: &str) -> bool {
+        match target.split_once("://") {
+            Some((scheme, _)) => {
+                let mut chars = scheme.chars();
+                matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+                    && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+            }
+            None => false,
+        }
+    }
+
+    /// Whether an include should be passed through verbatim, which is the case for files without
+    /// an AsciiDoc extension or if the `verbatim` option is set via `opts=verbatim`.
+    fn is_verbatim_include(target: &str, attributes: &str) -> bool {
+        const ASCIIDOC_EXTENSIONS: &[&str] = &["adoc", "asciidoc", "asc", "ad"];
+        // the extension of a URI is that of its path, without the query or fragment
+        let target = match Self::is_uri(target) {
+            true => target.split(&['?', '#'][..]).next().unwrap_or(target),
+            false => target,
+        };
+        let is_asciidoc = match target.rsplit_once('.') {
+            Some((_, extension)) => ASCIIDOC_EXTENSIONS.contains(&extension),
+            None => false,
+        };
+        let opts = Self::include_attribute(attributes, "opts")
+            .or_else(|| Self::include_attribute(attributes, "options"));
+        !is_asciidoc
+            || matches!(opts, Some(opts) if opts.split(',').any(|opt| opt.trim() == "verbatim"))
+    }
+
+    /// Resolves a `leveloffset` value, which is relative if prefixed by a sign.
+    fn resolve_leveloffset(current: isize, offset: &str) -> isize {
+        match offset.parse::<isize>() {
+            Ok(relative) if offset.starts_with(&['+', '-'][..]) => current + relative,
+            Ok(absolute) => absolute,
+            Err(_) => current,
+        }
+    }
+
+    /// Shifts the level of a section title line by `leveloffset`, clamping the result to the
+    /// valid levels 0 to 5.
+    fn offset_section_title(line: &str, leveloffset: isize) -> Cow<'_, str> {
+        let level = line.bytes().take_while(|&c| c == b'=').count();
+        if leveloffset == 0 || level == 0 || level > 6 || !line[level..].starts_with(' ') {
+            return Cow::Borrowed(line);
+        }
+        let new_level = (level as isize - 1 + leveloffset).clamp(0, 5) as usize;
+        Cow::Owned(format!("{}{}", "=".repeat(new_level + 1), &line[level..]))
+    }
+
+    /// Whether lines can be skipped without parsing them, which is always the case outside of
+    /// tests.
+    fn fast_skip(&self) -> bool {
+        #[cfg(test)]
+        return self.fast_skip;
+        #[cfg(not(test))]
+        true
+    }
+
+    /// A cheap check for whether `line` may be a conditional directive.
+    fn is_conditional_directive(line: &str) -> bool {
+        line.starts_with("if") || line.starts_with("endif::")
+    }
+
+    /// Parses a preprocessor directive spanning the whole line. Like in Asciidoctor, a directive
+    /// has to start at the beginning of the line, while trailing whitespace after its closing
+    /// `]` is ignored. Lines with any other content after the `]` are not directives.
+    #[allow(clippy::toplevel_ref_arg)]
     fn parse_pp_directive(line: &str) -> Option<PreprocessorDirective<'_>> {
         if line.starts_with('[') {
             return None;
@@ -215,7 +722,7 @@ where
 
         let ref path = |t| preceded(tag(t), take_till1(|c| c == '['));
         let ref path_opt = |t| preceded(tag(t), take_till(|c| c == '['));
-        let res = alt((
+        let directive = alt((
             map(pair(path("include::"), Self::attr_list), |(target, attributes)| {
                 PreprocessorDirective::Include { target, attributes }
             }),
@@ -225,13 +732,18 @@ where
             map(pair(path("ifndef::"), Self::attr_list_ifdef), |(targets, inline)| {
                 PreprocessorDirective::IfNotDef { targets, inline }
             }),
+            // an `ifdef` or `ifndef` without targets is an error rather than plain text
+            map(pair(alt((tag("ifdef::"), tag("ifndef::"))), Self::attr_list), |_| {
+                PreprocessorDirective::Malformed
+            }),
             map(pair(path_opt("endif::"), Self::attr_list), |(targets, _)| {
                 PreprocessorDirective::EndIf { targets }
             }),
-            map(pair(path_opt("ifeval::"), Self::attr_list), |(_, attribute)| {
-                PreprocessorDirective::IfEval { attribute }
+            map(pair(path_opt("ifeval::"), Self::attr_list), |(_, expression)| {
+                PreprocessorDirective::IfEval { expression }
             }),
-        ))(line);
+        ));
+        let res = terminated(directive, pair(take_while(char::is_whitespace), eof))(line);
         res.ok().map(|(_, pp)| pp)
     }
 
@@ -247,13 +759,61 @@ where
     }
 }
 
+/// The condition of a conditional directive
+#[derive(Debug, PartialEq, Eq)]
+enum Condition<'a> {
+    /// The targets of an `ifdef`, like `a,b+c`, being set
+    Defined(&'a str),
+    /// An attribute having the given value, like `ifeval::["{backend}" == "html5"]`
+    Equals { name: &'a str, value: &'a str },
+}
+
+impl<'a> Condition<'a> {
+    /// Parses the expression of an `ifeval` comparing an attribute reference with a value, like
+    /// `"{backend}" == "html5"` or `{sectnumlevels} != 3`. Returns the condition and whether it
+    /// is negated.
+    fn parse_expression(expression: &'a str) -> Option<(Self, bool)> {
+        let (lhs, rhs, negated) = match expression.split_once("==") {
+            Some((lhs, rhs)) => (lhs, rhs, false),
+            None => {
+                let (lhs, rhs) = expression.split_once("!=")?;
+                (lhs, rhs, true)
+            }
+        };
+        let unquote = |operand: &'a str| {
+            let operand = operand.trim();
+            operand.strip_prefix('"').and_then(|it| it.strip_suffix('"')).unwrap_or(operand)
+        };
+        let reference = |operand: &'a str| operand.strip_prefix('{')?.strip_suffix('}');
+        let (lhs, rhs) = (unquote(lhs), unquote(rhs));
+        let condition = match (reference(lhs), reference(rhs)) {
+            (Some(name), _) => Condition::Equals { name, value: rhs },
+            (None, Some(name)) => Condition::Equals { name, value: lhs },
+            (None, None) => return None,
+        };
+        Some((condition, negated))
+    }
+}
+
+/// Evaluates the condition of a conditional directive against the currently set attributes.
+/// For `ifdef` targets, `+` binds tighter than `,`, so `a,b+c` means `a` or both `b` and `c`.
+fn evaluate_condition(condition: &Condition<'_>, attributes: &AttributeMap) -> bool {
+    match *condition {
+        Condition::Defined(targets) => {
+            targets.split(',').any(|all| all.split('+').all(|target| attributes.contains(target)))
+        }
+        Condition::Equals { name, value } => attributes.get(name) == Some(value),
+    }
+}
+
 #[derive(Debug)]
 enum PreprocessorDirective<'a> {
     Include { target: &'a str, attributes: &'a str },
     IfDef { targets: &'a str, inline: Option<&'a str> },
     IfNotDef { targets: &'a str, inline: Option<&'a str> },
-    IfEval { attribute: &'a str },
+    IfEval { expression: &'a str },
     EndIf { targets: &'a str },
+    Malformed,
 }
 
 #[cfg(test)]
@@ -265,6 +825,7 @@ mod test {
     use expect_test::{expect, Expect};
 
     use super::*;
+    use crate::ast::{Block, Context};
 
     fn no_include_cb(_: &AttributeMap, _: &str) -> Result<String, Infallible> {
         panic!("test used include directive which wasn't expected")
@@ -311,6 +872,34 @@ mod test {
         );
     }
 
+    #[test]
+    pub fn test_bom() {
+        check(
+            "\u{feff}= Document Title\n:toc:\n",
+            no_include_cb,
+            expect![[r#"
+                = Document Title
+                :toc:
+            "#]],
+        );
+    }
+
+    #[test]
+    pub fn test_bom_include() {
+        let mut files = HashMap::new();
+        files.insert("chapter.adoc", "\u{feff}== Chapter\n\nContent.\n");
+        let root = "= Document\n\ninclude::chapter.adoc[]\n";
+        let source = DocumentSource::new(root, |_: &_, path: &str| -> Result<_, ()> {
+            Ok(files[path].to_owned())
+        })
+        .unwrap();
+        assert_eq!(source.amalgamated(), "= Document\n\n== Chapter\n\nContent.\n");
+        // offsets into the included file still account for the skipped byte-order mark
+        let start = source.amalgamated().find("Chapter").unwrap();
+        let (file, range) = source.original_range(start..start + "Chapter".len()).unwrap();
+        assert_eq!(&files[source.file_target(file).unwrap()][range], "Chapter");
+    }
+
     #[test]
     pub fn test_include() {
         let mut files = HashMap::new();
@@ -349,6 +938,256 @@ include::empty[]
         );
     }
 
+    #[test]
+    pub fn test_document_source() {
+        let mut files = HashMap::new();
+        files.insert("chapter.adoc", "== Chapter\n\nSome content.");
+        let fixture = "= Book\n\ninclude::chapter.adoc[]\n\nThe end.";
+        let source = DocumentSource::new(fixture, |_: &_, path: &_| -> Result<_, ()> {
+            Ok(files[path].into())
+        })
+        .unwrap();
+        let expect = expect![[r#"
+            = Book
+
+            == Chapter
+
+            Some content.
+
+            The end."#]];
+        expect.assert_eq(source.amalgamated());
+        expect.assert_eq(&source.into_string());
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    pub fn test_from_bytes() {
+        use crate::encoding::Encoding;
+
+        let bytes = b"= R\xe9sum\xe9\n\nNa\xefve text.\n";
+        let source = DocumentSource::from_bytes(bytes, Encoding::Latin1, no_include_cb).unwrap();
+        assert_eq!(source.amalgamated(), "= Résumé\n\nNaïve text.\n");
+        let (doc, errors) = crate::parser::parse_document_lossy(source.amalgamated());
+        assert_eq!(errors, []);
+        assert_eq!(doc.header.unwrap().title.text(), "Résumé");
+    }
+
+    #[test]
+    pub fn test_original_range() {
+        let mut files = HashMap::new();
+        files.insert("chapter.adoc", "== Chapter\n\nA paragraph\nin the chapter.\n");
+        let root = "= Document\n\ninclude::chapter.adoc[leveloffset=+1]\n\nThe end.";
+        let source = DocumentSource::new(root, |_: &_, path: &str| -> Result<_, ()> {
+            Ok(files[path].to_owned())
+        })
+        .unwrap();
+        let (doc, _) = crate::parser::parse_document_lossy(source.amalgamated());
+        let (title, paragraph) = match &doc.content[..] {
+            [Block { context: Context::SectionTitle(title, blocks), .. }, ..] => {
+                match &blocks[..] {
+                    [Block { context: Context::Paragraph(paragraph), .. }, ..] => {
+                        (title.content, *paragraph)
+                    }
+                    _ => panic!("expected a paragraph in the section"),
+                }
+            }
+            _ => panic!("expected a section"),
+        };
+
+        let (file, range) = source.original_range(paragraph.byte_range()).unwrap();
+        assert_eq!(source.file_target(file), Some("chapter.adoc"));
+        assert_eq!(&files["chapter.adoc"][range], "A paragraph\nin the chapter.");
+        // the section title was shifted by the level offset
+        let (file, range) = source.original_range(title.byte_range()).unwrap();
+        assert_eq!(&files[source.file_target(file).unwrap()][range], "Chapter");
+        let end = source.amalgamated().len();
+        let (file, range) = source.original_range(end - "The end.".len()..end).unwrap();
+        assert_eq!(source.file_target(file), None);
+        assert_eq!(&root[range], "The end.");
+    }
+
+    #[test]
+    pub fn test_include_in_table_cell() {
+        let mut files = HashMap::new();
+        files.insert("cell.adoc", "A paragraph.\n\n* a list\n* in a cell");
+        let root = "|===\n|Plain a|\ninclude::cell.adoc[]\n|===\n";
+        let source = DocumentSource::new(root, |_: &_, path: &str| -> Result<_, ()> {
+            Ok(files[path].to_owned())
+        })
+        .unwrap();
+        let (doc, errors) = crate::parser::parse_document_lossy(source.amalgamated());
+        assert_eq!(errors, []);
+        let cell = match &doc.content[..] {
+            [Block { context: Context::Table(table), .. }] => table.rows[0][1].clone(),
+            _ => panic!("expected a table"),
+        };
+        assert_eq!(cell.style, Some(crate::ast::ColumnStyle::AsciiDoc));
+        expect![[r#"
+            [
+                Block {
+                    context: Paragraph(
+                        "A paragraph.",
+                    ),
+                    title: None,
+                    attributes: {},
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+                Block {
+                    context: List(
+                        List {
+                            items: [
+                                ListItem {
+                                    kind: Unordered,
+                                    level: 1,
+                                    paragraph: [
+                                        Text(
+                                            "a list",
+                                        ),
+                                    ],
+                                    blocks: [],
+                                    bibliography: None,
+                                },
+                                ListItem {
+                                    kind: Unordered,
+                                    level: 1,
+                                    paragraph: [
+                                        Text(
+                                            "in a cell",
+                                        ),
+                                    ],
+                                    blocks: [],
+                                    bibliography: None,
+                                },
+                            ],
+                            style: None,
+                            start: None,
+                        },
+                    ),
+                    title: None,
+                    attributes: {},
+                    id: None,
+                    roles: [],
+                    options: [],
+                    callouts: [],
+                },
+            ]
+        "#]]
+        .assert_debug_eq(&cell.blocks());
+    }
+
+    #[test]
+    pub fn test_verbatim_include() {
+        let mut files = HashMap::new();
+        files.insert("main.rs", "ifdef::x[]\nfn main() {}\ninclude::other.rs[]");
+        files.insert("snippet.adoc", "ifdef::x[]\nskipped\nendif::x[]\nkept");
+        check(
+            "include::main.rs[]\ninclude::snippet.adoc[]\ninclude::snippet.adoc[opts=verbatim]",
+            |_: &_, path: &_| -> Result<_, ()> { Ok(files[path].into()) },
+            expect![[r#"
+                ifdef::x[]
+                fn main() {}
+                include::other.rs[]
+                kept
+                ifdef::x[]
+                skipped
+                endif::x[]
+                kept"#]],
+        );
+    }
+
+    #[test]
+    pub fn test_tagged_include() {
+        let mut files = HashMap::new();
+        files.insert(
+            "main.rs",
+            "use std::io;\n// tag::main[]\nfn main() {}\n// end::main[]\nfn other() {}",
+        );
+        files.insert(
+            "script.sh",
+            "#!/bin/sh\n# tag::setup[]\nset -e\n# tag::inner[]\ncd /tmp\n# end::inner[]\n# end::setup[]",
+        );
+        files.insert(
+            "page.html",
+            "<html>\n<!-- tag::body[] -->\n<body></body>\n<!-- end::body[] -->\n</html>",
+        );
+        check(
+            "include::main.rs[tag=main]\ninclude::script.sh[tags=setup]\ninclude::page.html[tag=body]",
+            |_: &_, path: &_| -> Result<_, ()> { Ok(files[path].into()) },
+            expect![[r#"
+                fn main() {}
+                set -e
+                cd /tmp
+                <body></body>"#]],
+        );
+    }
+
+    #[test]
+    pub fn test_safe_mode() {
+        let cb = |_: &_, path: &str| -> Result<_, ()> { Ok(format!("contents of {}", path)) };
+        let fixture = "include::../secrets.adoc[]";
+        let mut pp = Preprocessor::new(fixture, cb, <_>::default());
+        pp.safe_mode = SafeMode::Secure;
+        expect![[r#"
+            Err(
+                IncludeNotPermitted {
+                    target: "../secrets.adoc",
+                },
+            )
+        "#]]
+        .assert_debug_eq(&pp.amalgamate());
+
+        let mut pp = Preprocessor::new("include::/etc/passwd[]", cb, <_>::default());
+        pp.safe_mode = SafeMode::Server;
+        assert!(matches!(pp.amalgamate(), Err(PreprocessError::IncludeNotPermitted { .. })));
+
+        let mut pp = Preprocessor::new(fixture, cb, <_>::default());
+        pp.safe_mode = SafeMode::Unsafe;
+        pp.amalgamate().unwrap();
+        expect!["contents of ../secrets.adoc"].assert_eq(&pp.amalgamated);
+    }
+
+    #[test]
+    pub fn test_uri_include() {
+        let fixture = "include::https://example.org/docs/../snippet.adoc?raw=true[leveloffset=+1]";
+        let cb = |_: &_, path: &str| -> Result<_, ()> {
+            assert_eq!(path, "https://example.org/docs/../snippet.adoc?raw=true");
+            Ok("= Snippet\nifdef::allow-uri-read[]\nallowed\nendif::[]".to_owned())
+        };
+        let mut pp = Preprocessor::new(fixture, cb, <_>::default());
+        pp.safe_mode = SafeMode::Secure;
+        assert!(matches!(pp.amalgamate(), Err(PreprocessError::IncludeNotPermitted { .. })));
+
+        let attributes = vec![("allow-uri-read", "")].into_iter().collect();
+        let mut pp = Preprocessor::new(fixture, cb, attributes);
+        pp.safe_mode = SafeMode::Secure;
+        pp.amalgamate().unwrap();
+        expect![[r#"
+            == Snippet
+            allowed"#]]
+        .assert_eq(&pp.amalgamated);
+    }
+
+    #[test]
+    pub fn test_missing_include() {
+        let mut pp = Preprocessor::new(
+            "include::chapters/missing.adoc[]",
+            |_: &_, path: &str| -> Result<String, String> { Err(format!("{} not found", path)) },
+            <_>::default(),
+        );
+        expect![[r#"
+            Err(
+                IncludeError {
+                    target: "chapters/missing.adoc",
+                    source: "chapters/missing.adoc not found",
+                },
+            )
+        "#]]
+        .assert_debug_eq(&pp.amalgamate());
+    }
+
     #[test]
     pub fn test_recursive_include() {
         let mut files = HashMap::new();
@@ -367,6 +1206,369 @@ include::empty[]
         );
     }
 
+    #[test]
+    pub fn test_include_leveloffset() {
+        let mut files = HashMap::new();
+        files.insert("chapter.adoc", "= Chapter\n\n== Section\n\ntext\n");
+        files.insert("deep.adoc", "=== Deep\n== Less deep");
+        files.insert("deeper.adoc", "==== Deeper\n===== Deepest");
+        let cb = |_: &_, path: &_| -> Result<_, ()> { Ok(files[path].into()) };
+        check(
+            "= Book\ninclude::chapter.adoc[leveloffset=+1]\n== Appendix\n",
+            cb,
+            expect![[r#"
+                = Book
+                == Chapter
+
+                === Section
+
+                text
+
+                == Appendix
+            "#]],
+        );
+        check(
+            "include::deep.adoc[leveloffset=-1]\n== After",
+            cb,
+            expect![[r#"
+                == Deep
+                = Less deep
+                == After"#]],
+        );
+        // levels are clamped to the range of valid section levels
+        check(
+            "include::chapter.adoc[leveloffset=-1]\ninclude::deeper.adoc[leveloffset=+2]",
+            cb,
+            expect![[r#"
+                = Chapter
+
+                = Section
+
+                text
+
+                ====== Deeper
+                ====== Deepest"#]],
+        );
+        check(
+            ":leveloffset: +1\ninclude::deep.adoc[]\n:leveloffset!:\n== After",
+            cb,
+            expect![[r#"
+                :leveloffset: +1
+                ==== Deep
+                === Less deep
+                :leveloffset!:
+                == After"#]],
+        );
+    }
+
+    #[test]
+    pub fn test_unbalanced_conditional() {
+        let mut files = HashMap::new();
+        files.insert("open.adoc", "ifdef::foo[]\nfoo");
+        files.insert("close.adoc", "bar\nendif::[]");
+        files.insert("balanced.adoc", "ifndef::foo[]\nbaz\nendif::[]");
+        let mut cb = |_: &_, path: &_| -> Result<String, ()> { Ok(files[path].into()) };
+
+        let mut pp = Preprocessor::new("include::open.adoc[]\nendif::[]", &mut cb, <_>::default());
+        expect![[r#"
+            Err(
+                UnbalancedConditional {
+                    file: Some(
+                        "open.adoc",
+                    ),
+                },
+            )
+        "#]]
+        .assert_debug_eq(&pp.amalgamate());
+
+        let mut pp =
+            Preprocessor::new("ifndef::foo[]\ninclude::close.adoc[]\nqux", &mut cb, <_>::default());
+        expect![[r#"
+            Err(
+                UnbalancedConditional {
+                    file: Some(
+                        "close.adoc",
+                    ),
+                },
+            )
+        "#]]
+        .assert_debug_eq(&pp.amalgamate());
+
+        let mut pp = Preprocessor::new("ifdef::foo[]\nfoo", &mut cb, <_>::default());
+        expect![[r#"
+            Err(
+                UnbalancedConditional {
+                    file: None,
+                },
+            )
+        "#]]
+        .assert_debug_eq(&pp.amalgamate());
+
+        check("ifndef::foo[]\ninclude::balanced.adoc[]\nendif::[]", &mut cb, expect![[r#"baz"#]]);
+    }
+
+    #[test]
+    pub fn test_targeted_endif() {
+        let fixture = r#"ifdef::outer[]
+outer
+ifndef::inner[]
+inner
+endif::outer[]
+after outer
+endif::inner[]
+end"#;
+        check(
+            fixture,
+            |_: &_, _: &_| -> Result<_, ()> { unreachable!() },
+            expect![[r#"
+                after outer
+                end"#]],
+        );
+
+        let mut attributes = AttributeMap::default();
+        attributes.insert("outer", "");
+        check_with_attributes(
+            fixture,
+            |_: &_, _: &_| -> Result<_, ()> { unreachable!() },
+            attributes,
+            expect![[r#"
+                outer
+                inner
+                after outer
+                end"#]],
+        );
+
+        let mut pp = Preprocessor::new(
+            "ifdef::foo[]\nendif::bar[]",
+            |_: &_, _: &_| -> Result<String, ()> { unreachable!() },
+            <_>::default(),
+        );
+        expect![[r#"
+            Err(
+                UnmatchedEndIf {
+                    targets: "bar",
+                    file: None,
+                },
+            )
+        "#]]
+        .assert_debug_eq(&pp.amalgamate());
+    }
+
+    #[test]
+    pub fn test_mixed_conditional_targets() {
+        let fixture = r#"ifdef::a[]
+a
+endif::[]
+ifdef::a+b[]
+a+b
+endif::[]
+ifdef::a+c[]
+a+c
+endif::[]
+ifdef::c,d[]
+c,d
+endif::[]
+ifdef::c,a+b[]
+c,a+b
+endif::[]
+ifdef::c,a+d[]
+c,a+d
+endif::[]
+ifdef::a+c,b[]
+a+c,b
+endif::[]
+ifdef::a+c,d[]
+a+c,d
+endif::[]
+"#;
+        let attributes = vec![("a", ""), ("b", "")].into_iter().collect();
+        check_with_attributes(
+            fixture,
+            no_include_cb,
+            attributes,
+            expect![[r#"
+                a
+                a+b
+                c,a+b
+                a+c,b
+            "#]],
+        );
+    }
+
+    #[test]
+    pub fn test_evaluate_condition() {
+        let attributes: AttributeMap =
+            vec![("backend", "html5"), ("toc", "")].into_iter().collect();
+        let holds = |condition| evaluate_condition(&condition, &attributes);
+        assert!(holds(Condition::Defined("toc")));
+        assert!(holds(Condition::Defined("backend+toc")));
+        assert!(!holds(Condition::Defined("icons")));
+        assert!(!holds(Condition::Defined("icons+toc")));
+        assert!(holds(Condition::Equals { name: "backend", value: "html5" }));
+        assert!(!holds(Condition::Equals { name: "backend", value: "pdf" }));
+        assert!(!holds(Condition::Equals { name: "icons", value: "" }));
+        assert!(holds(Condition::Equals { name: "toc", value: "" }));
+
+        assert_eq!(
+            Condition::parse_expression(r#""{backend}" == "html5""#),
+            Some((Condition::Equals { name: "backend", value: "html5" }, false))
+        );
+        assert_eq!(
+            Condition::parse_expression("3 != {sectnumlevels}"),
+            Some((Condition::Equals { name: "sectnumlevels", value: "3" }, true))
+        );
+        assert_eq!(Condition::parse_expression("1 < 2"), None);
+    }
+
+    #[test]
+    pub fn test_ifeval() {
+        let fixture = r#"ifeval::["{backend}" == "html5"]
+html
+endif::[]
+ifeval::[{backend} != html5]
+not html
+endif::[]
+"#;
+        let attributes = vec![("backend", "html5")].into_iter().collect();
+        check_with_attributes(
+            fixture,
+            no_include_cb,
+            attributes,
+            expect![[r#"
+                html
+            "#]],
+        );
+    }
+
+    #[test]
+    pub fn test_ifeval_unevaluable() {
+        // expressions that can't be evaluated keep their content and are reported
+        let fixture =
+            "ifeval::[{sectnumlevels} > 2]\ndeep\nendif::[]\nifeval::[1 == 1]\nequal\nendif::[]\n";
+        let source = DocumentSource::new(fixture, no_include_cb).unwrap();
+        expect![[r#"
+            deep
+            equal
+        "#]]
+        .assert_eq(source.amalgamated());
+        expect![[r#"
+            [
+                UnevaluableExpression {
+                    expression: "{sectnumlevels} > 2",
+                    line: 1,
+                    file: None,
+                },
+                UnevaluableExpression {
+                    expression: "1 == 1",
+                    line: 4,
+                    file: None,
+                },
+            ]
+        "#]]
+        .assert_debug_eq(&source.warnings());
+    }
+
+    #[test]
+    pub fn test_directive_whitespace() {
+        // trailing spaces and tabs are ignored, leading ones or trailing text make a line text
+        let fixture = concat!(
+            "ifdef::a[] \nvisible\nendif::[]\t\n",
+            "ifndef::a[]  \t\nhidden\nendif::[]\n",
+            "  ifdef::b[]\nendif::[] text\n",
+        );
+        let attributes = vec![("a", "")].into_iter().collect();
+        check_with_attributes(
+            fixture,
+            no_include_cb,
+            attributes,
+            expect![[r#"
+                visible
+                  ifdef::b[]
+                endif::[] text
+            "#]],
+        );
+    }
+
+    #[test]
+    pub fn test_malformed_directive() {
+        let mut pp = Preprocessor::new("text\nifdef::[]\nendif::[]", no_include_cb, <_>::default());
+        expect![[r#"
+            Err(
+                MalformedDirective {
+                    line: 2,
+                    file: None,
+                },
+            )
+        "#]]
+        .assert_debug_eq(&pp.amalgamate());
+
+        let mut pp = Preprocessor::new("ifndef::[text]", no_include_cb, <_>::default());
+        expect![[r#"
+            Err(
+                MalformedDirective {
+                    line: 1,
+                    file: None,
+                },
+            )
+        "#]]
+        .assert_debug_eq(&pp.amalgamate());
+
+        let attributes = vec![("a", "")].into_iter().collect();
+        check_with_attributes(
+            "ifdef::a[]\ntext\nendif::[]",
+            no_include_cb,
+            attributes,
+            expect!["text"],
+        );
+    }
+
+    #[test]
+    pub fn test_skip_nested_conditionals() {
+        // every other nesting level is active, lines of a level are visible if all of the
+        // enclosing conditionals are
+        let mut attributes = AttributeMap::default();
+        let mut fixture = String::new();
+        let mut expected = Vec::new();
+        let depth = 50;
+        for level in 0..depth {
+            if level % 2 == 0 {
+                attributes.insert(format!("attr{}", level), "");
+            }
+            fixture.push_str(&format!("ifdef::attr{}[]\n", level));
+            fixture.push_str(&format!("level {}\n", level));
+            if level == 0 {
+                expected.push(format!("level {}", level));
+                fixture.push_str("include::part.adoc[]\n");
+                expected.push("part".to_owned());
+            } else {
+                fixture.push_str("include::never.adoc[]\n");
+            }
+        }
+        for level in (0..depth).rev() {
+            fixture.push_str(&format!("after level {}\nendif::attr{}[]\n", level, level));
+            if level == 0 {
+                expected.push(format!("after level {}", level));
+            }
+        }
+        fixture.push_str("end");
+        expected.push("end".to_owned());
+
+        let amalgamate = |fast_skip| {
+            let include_cb = |_: &_, target: &_| -> Result<String, ()> {
+                assert_eq!(target, "part.adoc");
+                Ok("part".to_owned())
+            };
+            let mut pp = Preprocessor::new(fixture.as_str(), include_cb, attributes.clone());
+            pp.fast_skip = fast_skip;
+            pp.amalgamate().unwrap();
+            (pp.amalgamated, pp.source_map)
+        };
+        let (amalgamated, source_map) = amalgamate(true);
+        assert_eq!(amalgamated, expected.join("\n"));
+        // skipping lines without parsing them has to behave like the full directive handling
+        assert_eq!((amalgamated, source_map), amalgamate(false));
+    }
+
     #[test]
     pub fn test_ifdef_inline() {
         let fixture = r"ifdef::foo[This is an inline ifdef]";
@@ -18,6 +18,65 @@ impl<'a> Span<'a> {
     pub fn text(&self) -> &'a str {
         (self.0).fragment()
     }
+
+    /// The byte range of this span within the input it was parsed from, see
+    /// [`DocumentSource::original_range`](crate::source::DocumentSource::original_range) for
+    /// mapping it back to the original files.
+    pub fn byte_range(&self) -> Range<usize> {
+        let start = self.location_offset();
+        start..start + self.len()
+    }
+
+    /// The offset just past the end of this span within the input it was parsed from.
+    pub fn end_offset(&self) -> usize {
+        self.location_offset() + self.len()
+    }
+
+    /// Whether this span lies within `parent`. Both spans have to stem from the same input.
+    pub fn within(&self, parent: &Span<'_>) -> bool {
+        parent.location_offset() <= self.location_offset()
+            && self.end_offset() <= parent.end_offset()
+    }
+
+    /// The span covering `part`, a subslice of this span's text, or `None` if `part` does not lie
+    /// within it. This recovers the position of `&str`s taken out of a span, like the values of an
+    /// [`AttributeList`](crate::ast::AttributeList).
+    pub fn sub_span(&self, part: &'a str) -> Option<Span<'a>> {
+        let text = self.text();
+        let start = (part.as_ptr() as usize).checked_sub(text.as_ptr() as usize)?;
+        let end = start + part.len();
+        (end <= text.len()).then(|| self.slice(start..end))
+    }
+
+    /// Iterates over the lines of this span, each without its trailing `\n`. Unlike `str::lines`,
+    /// the lines keep their position within the input.
+    pub fn lines(&self) -> impl Iterator<Item = Span<'a>> {
+        let span = *self;
+        let mut start = 0;
+        std::iter::from_fn(move || {
+            if start >= span.len() {
+                return None;
+            }
+            let end = span.text()[start..].find('\n').map_or(span.len(), |idx| start + idx);
+            let line = span.slice(start..end);
+            start = end + 1;
+            Some(line)
+        })
+    }
+
+    /// Removes leading whitespace, keeping the offset of the remaining text intact.
+    pub fn trim_start(&self) -> Self {
+        self.slice(self.len() - self.text().trim_start().len()..)
+    }
+
+    /// Removes trailing whitespace, including newlines.
+    pub fn trim_end(&self) -> Self {
+        self.slice(..self.text().trim_end().len())
+    }
+
+    pub fn trim(&self) -> Self {
+        self.trim_start().trim_end()
+    }
 }
 
 impl<'a> From<&'a str> for Span<'a> {
@@ -144,3 +203,43 @@ impl<'a> Offset for Span<'a> {
         self.0.offset(&second.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nom::Slice;
+
+    use super::Span;
+
+    #[test]
+    fn test_within() {
+        let input = Span::new("Some text\nmore text\n");
+        let line = input.slice(10..19);
+        assert_eq!(line.text(), "more text");
+        assert_eq!(line.end_offset(), 19);
+        assert!(line.within(&input));
+        assert!(input.within(&input));
+        assert!(line.slice(5..).within(&line));
+        assert!(input.slice(20..).within(&input));
+
+        // spans reaching past either end of their parent escape it
+        assert!(!input.within(&line));
+        assert!(!input.slice(5..12).within(&line));
+        assert!(!input.slice(15..).within(&line));
+    }
+
+    #[test]
+    fn test_lines() {
+        let input = Span::new("first\nsecond\n\nlast");
+        let lines: Vec<_> = input.lines().collect();
+        assert_eq!(
+            lines.iter().map(Span::text).collect::<Vec<_>>(),
+            ["first", "second", "", "last"]
+        );
+        assert_eq!(lines[1].location_offset(), 6);
+        assert_eq!(lines[1].location_line(), 2);
+        assert_eq!(lines[3].location_offset(), 14);
+
+        assert_eq!(Span::new("trailing\n").lines().count(), 1);
+        assert_eq!(Span::new("").lines().count(), 0);
+    }
+}